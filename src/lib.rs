@@ -3,6 +3,8 @@
 #![warn(clippy::map_unwrap_or)]
 #![warn(clippy::semicolon_if_nothing_returned)]
 #![warn(clippy::todo)]
+// packed lists cache their unpacked items in a OnceCell, which doesn't change their hash
+#![allow(clippy::mutable_key_type)]
 
 extern crate core; extern crate alloc;
 
@@ -10,13 +12,14 @@ extern crate core; extern crate alloc;
 #[cfg(test)] mod test;
 mod token; mod parse; #[macro_use] mod run;
 
-pub use run::{Env, Val, c64, io::{Interface, NoIO}};
+pub use run::{Env, Val, Error, ErrorKind, c64, io::{Interface, NoIO}};
 #[cfg(feature="std")] pub use run::io::{io_result, StdIO, FromIoWrite};
 
 /// owned byte string type. length will be the same as a Vec in 64bit archs
 #[cfg(feature="smallvec")] pub type Bstr = smallvec::SmallVec<[u8; 16]>;
 #[cfg(not(feature="smallvec"))] pub type Bstr = alloc::vec::Vec<u8>;
-pub use token::{rewrite, escape_1c, escape_2c};
+pub use token::{rewrite, escape_1c, escape_2c, Span};
+pub use parse::ParseError;
 
 pub fn bx<T>(x: T) -> alloc::boxed::Box<T> { alloc::boxed::Box::new(x) }
 
@@ -27,7 +30,7 @@ mod prelude {
     pub use alloc::rc::Rc;
     pub use core::iter;
     
-    pub use {b, or_nan, func};
+    pub use {b, or_nan, ok, func};
     pub use alloc::{vec, format};
     #[cfg(feature="smallvec")] pub use smallvec::smallvec as bstr;
     #[cfg(not(feature="smallvec"))] pub use vec as bstr;
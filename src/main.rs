@@ -1,5 +1,6 @@
 use std::{path::PathBuf, io::{Read, Write}, fs::File};
-use vemf::{Bstr, codepage, Val, Env, FromIoWrite, bx};
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use vemf::{Bstr, codepage, Val, Env, ErrorKind, FromIoWrite, bx};
 
 struct Options {
     filename: Option<PathBuf>,
@@ -10,6 +11,8 @@ struct Options {
     rewrite: bool,
     prompt: String,
     use_utf8: bool,
+    trace: bool,
+    fuel: Option<u64>,
     code: Option<String>
 }
 
@@ -34,6 +37,8 @@ fn parse_args() -> Options {
         rewrite: false,
         prompt: std::env::var("VEMF_PROMPT").unwrap_or_else(|_| String::from("    ")),
         use_utf8: true,
+        trace: false,
+        fuel: None,
         code: None,
     };
     _ = iter.next();
@@ -57,6 +62,9 @@ the script unchanged. sets α, β and δ accordingly.
   -p/--prompt <prompt>: repl only. use <prompt> as the input prompt
   -b: read file using the vemf codepage instead of utf-8
   -e <code>: execute <code> instead of reading file
+  -t/--trace: print stack traces for errors
+  --fuel <steps>: stop with an error after evaluating <steps> expressions or calls
+files imported with _ò are looked for next to the script, then in the directories in VEMF_PATH.
 ");
                 std::process::exit(0);
             },
@@ -64,6 +72,10 @@ the script unchanged. sets α, β and δ accordingly.
             Some("--no-stdlib")      => { opts.no_stdlib = true; }
             Some("-i" | "--inspect") => { opts.inspect   = true; }
             Some("-b")               => { opts.use_utf8  = false; }
+            Some("-t" | "--trace")   => { opts.trace     = true; }
+            Some("--fuel") => {
+                opts.fuel = iter.next().and_then(|x| x.parse().ok());
+            }
             Some("-p" | "--prompt") => {
                 opts.prompt = iter.next().unwrap_or_else(|| String::from(""));
             }
@@ -84,18 +96,34 @@ the script unchanged. sets α, β and δ accordingly.
     opts
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// enough stack for env.max_depth nested calls, which the main thread doesn't have
+const STACK_SIZE: usize = 1 << 30;
+
+fn main() -> Result {
+    std::thread::Builder::new().stack_size(STACK_SIZE).spawn(run)?
+        .join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+}
+
+fn run() -> Result {
     let opts = parse_args();
     let mut env = Env::new(bx(rand::thread_rng()));
     let mut code;
     if !opts.no_stdlib { env.include_stdlib(); }
+    env.trace = opts.trace;
+    env.fuel = opts.fuel;
     env.interface = bx(vemf::StdIO {});
     env.include_args(&opts.arguments);
+    if let Some(path) = std::env::var_os("VEMF_PATH") {
+        env.import_path = std::env::split_paths(&path).map(|x| x.to_string_lossy().into_owned()).collect();
+    }
     if let Some(c) = opts.code.as_ref() {
         code = c.as_bytes().to_vec();
     } else if let Some(path) = opts.filename.as_ref() {
         code = Vec::new();
         File::open(path)?.read_to_end(&mut code)?;
+        env.dir = path.parent().map_or_else(String::new, |x| x.to_string_lossy().into_owned());
     } else {
         repl(env, opts);
         return Ok(());
@@ -105,8 +133,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .ok_or("input contains characters outside codepage")?;
     }
     if opts.rewrite { rewrite(&code); return Ok(()) }
-    if let Err(x) = env.run_bytes(&code, &fmtstring(&opts.format)) {
-        std::process::exit(x)
+    if let Err(e) = env.run_bytes(&code, &fmtstring(&opts.format)) {
+        if e.kind != ErrorKind::Exit { eprintln!("{e}"); }
+        else if e.code != 0 { for t in &e.trace { eprintln!("  {t}"); } }
+        std::process::exit(e.code)
     };
     println!();
     if opts.inspect { repl(env, opts); }
@@ -116,6 +146,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 fn repl(mut env: Env, mut opts: Options) {
     println!("welcome to vemf repl. enjoy your stay");
     opts.format = String::from("1");
+    // ^C stops the running code, or exits if nothing is running
+    let cancel = Arc::new(AtomicBool::new(false));
+    let running = Arc::new(AtomicBool::new(false));
+    env.cancel = Some(Arc::clone(&cancel));
+    let (c, r) = (Arc::clone(&cancel), Arc::clone(&running));
+    _ = ctrlc::set_handler(move || if r.load(Ordering::Relaxed) {
+        c.store(true, Ordering::Relaxed)
+    } else { std::process::exit(130) });
     loop {
         print!("{}", opts.prompt);
         _ = std::io::stdout().flush();
@@ -160,7 +198,11 @@ fn repl(mut env: Env, mut opts: Options) {
         if opts.rewrite { println!(" r: {}", codepage::tochars(&vemf::rewrite(
             &codepage::tobytes(code.trim_end()).unwrap()
         )));}
+        env.fuel = opts.fuel;
+        running.store(true, Ordering::Relaxed);
         let val = env.include_string(&code);
+        running.store(false, Ordering::Relaxed);
+        cancel.store(false, Ordering::Relaxed);
         if !val.is_nan() {
             val.format(&mut FromIoWrite(std::io::stdout()), &fmtstring(&opts.format)).unwrap();
         }
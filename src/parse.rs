@@ -2,11 +2,34 @@ use alloc::fmt::Display;
 
 use crate::prelude::*;
 use crate::codepage::tochars;
-use crate::token::Tok::{self, Just};
+use crate::token::{Tok::{self, Just}, Span, Spanned};
 
 use num_complex::Complex64 as c64;
 
-fn step(t: &mut&[Tok]) { *t = &t[1..]; }
+fn step(t: &mut&[Spanned]) { *t = &t[1..]; }
+
+// span of the tokens consumed between `before` and `after`
+fn consumed(before: &[Spanned], after: &[Spanned]) -> Span {
+    before[0].1.to(before[before.len() - after.len() - 1].1)
+}
+
+/// a syntax error. `span` points at the offending token
+#[derive(Clone, Debug)]
+pub struct ParseError { pub span: Span, pub message: String }
+
+impl ParseError {
+    /// the message, followed by the offending line of `code` with a caret under the token
+    pub fn describe(&self, code: &[u8]) -> String {
+        let line = code.split(|&x| x == b'\n').nth(self.span.line - 1).unwrap_or(&[]);
+        format!("{}\n{}\n{}^", self.message, tochars(line), " ".repeat(self.span.col - 1))
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, m: &mut alloc::fmt::Formatter<'_>) -> alloc::fmt::Result {
+        write!(m, "{} at {}:{}", self.message, self.span.line, self.span.col)
+    }
+}
 
 // Value expression
 #[derive(Clone, Debug)]
@@ -14,26 +37,39 @@ pub enum Expr {
     Var(Bstr),
     Int(i64), Flt(c64),
     Snd(Vec<Expr>),  // strand
-    Afn1(Box<Expr>, Box<Expr>),            // 1-
-    Afn2(Box<Expr>, Box<Expr>, Box<Expr>), // 1+2
+    Afn1(Box<Expr>, Box<Expr>, Span),            // 1-
+    Afn2(Box<Expr>, Box<Expr>, Box<Expr>, Span), // 1+2
     SetVar(Bstr), MutVar(Bstr),
+    SetPat(Pat), // ─(ab)
     Aav1(           Bstr, Box<Expr>), // ╕@
     Aav2(Box<Expr>, Bstr, Box<Expr>), // ~╙↑
     Bind(Box<Expr>, Box<Expr>), // +1
     Trn2(Box<Expr>, Box<Expr>), // +/
     Trn3(Box<Expr>, Box<Expr>, Box<Expr>), // +/2
     Fork(Box<Expr>, Box<Expr>, Box<Expr>), // └+/~
-    Dfn { s: Vec<Stmt>, cap: HashSet<Bstr> },
+    Dfn { s: Vec<Stmt>, cap: HashSet<Bstr>, span: Span },
     Block(Vec<Stmt>),
+    Dict(Box<Expr>), // ⎕(k v k v)
 }
 use Expr::*;
 
+// statements that call functions keep their span, for errors
 #[derive(Debug, Clone)]
 pub enum Stmt {
     Discard(Expr), Return(Expr),
-    Loc(Expr, Bstr), Mut(Expr, Bstr),
+    Loc(Expr, Bstr), Mut(Expr, Bstr, Span), // 1↔x
     DelLoc(Bstr),    DelMut(Bstr),
-    Cond(Expr, Box<Stmt>),
+    LocPat(Expr, Pat),
+    Cond(Expr, Box<Stmt>, Span), // α=0?
+}
+
+/// what a destructuring assignment binds: names for the items of a list in order, `■` to skip
+/// one, nested patterns, and `,` before a name or `■` for all the items not taken by the others
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Pat {
+    Name(Bstr), Skip,
+    Rest(Option<Bstr>),
+    List(Vec<Pat>),
 }
 
 #[derive(Debug)]
@@ -53,10 +89,11 @@ fn fmt(&self, m: &mut alloc::fmt::Formatter<'_>) -> alloc::fmt::Result {
             for v in l { write!(m, "{v}")?; }
             write!(m, ")")?;
         Ok(()) },
-        Afn1(a, f) => write!(m, "({a} {f})"),
-        Afn2(a, f, b) => write!(m, "({a} {f} {b})"),
+        Afn1(a, f, _) => write!(m, "({a} {f})"),
+        Afn2(a, f, b, _) => write!(m, "({a} {f} {b})"),
         SetVar(v) => write!(m, "→{}", display(v)),
         MutVar(v) => write!(m, "↔{}", display(v)),
+        SetPat(p) => write!(m, "→{p}"),
         Aav1(v, g) => write!(m, "[•{} {}]", display(v), g),
         Aav2(f, v, g) => write!(m, "[{} ○{} {}]", f, display(v), g),
         Bind(f, b) => write!(m, "[{f} with {b}]"),
@@ -73,6 +110,7 @@ fn fmt(&self, m: &mut alloc::fmt::Formatter<'_>) -> alloc::fmt::Result {
             for v in s { write!(m, "{v} ")?; }
             write!(m, "]")?;
         Ok(()) }
+        Dict(e) => write!(m, "⎕{e}"),
     }
 }
 }
@@ -82,10 +120,29 @@ fn fmt(&self, m: &mut alloc::fmt::Formatter<'_>) -> alloc::fmt::Result { match s
     Stmt::Discard(e) => write!(m, "{e}·"),
     Stmt::Return(e) => write!(m, "{e}◘"),
     Stmt::Loc(e, f) => write!(m, "{}→{}·", e, display(f)),
-    Stmt::Mut(e, f) => write!(m, "{}↔{}·", e, display(f)),
+    Stmt::Mut(e, f, _) => write!(m, "{}↔{}·", e, display(f)),
     Stmt::DelLoc(f) => write!(m, "→{}·", display(f)),
     Stmt::DelMut(f) => write!(m, "↔{}·", display(f)),
-    Stmt::Cond(i, t) => write!(m, "{i}?{t}"),
+    Stmt::LocPat(e, p) => write!(m, "{e}→{p}·"),
+    Stmt::Cond(i, t, _) => write!(m, "{i}?{t}"),
+}}
+}
+
+impl Display for Pat {
+fn fmt(&self, m: &mut alloc::fmt::Formatter<'_>) -> alloc::fmt::Result { match self {
+    // names that aren't a single letter need a dot to be read back
+    Pat::Name(v) if matches!(v[..], [b'a'..=b'z']) => write!(m, "{}", display(v)),
+    Pat::Name(v) => write!(m, ".{}", display(v)),
+    Pat::Skip => write!(m, "■"),
+    Pat::Rest(v) => match v { Some(v) => write!(m, ",{}", Pat::Name(v.c())), None => write!(m, ",■") },
+    Pat::List(l) => {
+        write!(m, "(")?;
+        for (n, p) in l.iter().enumerate() {
+            if n != 0 { write!(m, " ")?; }
+            write!(m, "{p}")?;
+        }
+        write!(m, ")")
+    },
 }}
 }
 
@@ -95,50 +152,57 @@ fn display(bytes: &[u8]) -> String {
     } else { tochars(bytes) }
 }
 
-fn word_full(t: &mut&[Tok]) -> Option<(Role, Expr)> { word_cst(t, usize::MAX) }
+fn word_full(t: &mut&[Spanned]) -> Result<Option<(Role, Expr)>, ParseError> { word_cst(t, usize::MAX) }
 
-fn word_cst(t: &mut&[Tok], mut morphemes: usize) -> Option<(Role, Expr)>{
+fn word_cst(t: &mut&[Spanned], mut morphemes: usize) -> Result<Option<(Role, Expr)>, ParseError> {
     let ptr = &mut morphemes; word(t, ptr)
 }
 
-fn word(t: &mut&[Tok], morphemes: &mut usize) -> Option<(Role, Expr)> {
-    if *morphemes == 0 { return None }
-    let (rol, mut val) = match t.first()? {
+fn word(t: &mut&[Spanned], morphemes: &mut usize) -> Result<Option<(Role, Expr)>, ParseError> {
+    if *morphemes == 0 { return Ok(None) }
+    let before = *t;
+    let Some((tok, _)) = t.first() else { return Ok(None) };
+    let (rol, mut val) = match tok {
         Tok::VSet(v) => { step(t); (Verb, SetVar(v.c())) },
+        Tok::PSet => { step(t); (Verb, SetPat(pattern(t, before[0].1)?)) },
         Tok::VMut(v) => { step(t); (Verb, MutVar(v.c())) },
         Tok::VAv1(name) => { step(t);
-            let word = word(t, morphemes);
+            let word = word(t, morphemes)?;
             match word {
                 Some((_role, word)) => (Verb, Aav1(name.c(), bx(word))),
                 None => (Verb, Var(name.c())),
             }
         },
         Just(b!('└')) => { step(t);
-            let a = word_full(t).map_or(NAN, |x| x.1);
-            let f = word_full(t).map_or(NAN, |x| x.1);
-            let b = word(t, morphemes).map_or(NAN, |x| x.1);
+            let a = word_full(t)?.map_or(NAN, |x| x.1);
+            let f = word_full(t)?.map_or(NAN, |x| x.1);
+            let b = word(t, morphemes)?.map_or(NAN, |x| x.1);
             (Verb, Fork(bx(a), bx(f), bx(b)))
         },
         Just(b'{') => { step(t);
-            let s = block(t);
+            let s = block(t)?;
             let mut vars = HashSet::new();
             for i in &s { i.capture(&mut vars); }
-            if let Some(Just(b'}')) = t.first() { step(t); }
-            (Verb, Dfn {s, cap: vars})
+            if let Some((Just(b'}'), _)) = t.first() { step(t); }
+            (Verb, Dfn {s, cap: vars, span: consumed(before, t)})
         },
         Just(b'[') => { step(t);
-            let s = block(t);
-            if let Some(Just(b']')) = t.first() { step(t); }
+            let s = block(t)?;
+            if let Some((Just(b']'), _)) = t.first() { step(t); }
             (Noun, Block(s))
         }
         Tok::VVerb(v) => { step(t); (Verb, Var(v.c())) },
         Just(b'(') => { step(t);
-            let expr = phrase_to_expr(phrase(t)).unwrap_or(Snd(vec![]));
-            if let Some(Just(b')')) = t.first() { step(t); }
+            let expr = phrase_to_expr(phrase(t)?).unwrap_or(Snd(vec![]));
+            if let Some((Just(b')'), _)) = t.first() { step(t); }
             (Noun, expr)
         },
+        Just(b!('⎕')) => { step(t);
+            let arg = word_full(t)?.map_or(Snd(vec![]), |x| x.1);
+            (Noun, Dict(bx(arg)))
+        },
         Just(b!('♪')) => { step(t);
-            let (rol, arg) = word_full(t).unwrap_or((Noun, NAN));
+            let (rol, arg) = word_full(t)?.unwrap_or((Noun, NAN));
             (Noun, match rol {
                 Noun => Snd(vec![arg]),
                 Verb => arg
@@ -147,61 +211,89 @@ fn word(t: &mut&[Tok], morphemes: &mut usize) -> Option<(Role, Expr)> {
         Just(s @ b!('┘''│''├''╞''╟''╠''┤''╡''╢''╣')) => { step(t);
             macro_rules! bl { [$($b:tt)+] => {[$(b!($b)),+]} }
             let p = phrase_by_morphemes(t, 
-                bl!['┘''│''├''╞''╟''╠''┤''╡''╢''╣'].iter().position(|x| x == s).unwrap() + 1);
+                bl!['┘''│''├''╞''╟''╠''┤''╡''╢''╣'].iter().position(|x| x == s).unwrap() + 1)?;
             let e = phrase_to_expr(p);
             (if *s == b!('┘') {Verb} else {Noun}, e.unwrap_or(NAN))
         },
-        tok => if let Some(p) = value_token(tok.c()) { step(t); (Noun, p) } else {return None}
+        tok => if let Some(p) = value_token(tok.c()) { step(t); (Noun, p) } else { return Ok(None) }
     };
-    if *morphemes > 1 { if let Some(Tok::VAv2(l, n)) = t.first() {
+    if *morphemes > 1 { if let Some((Tok::VAv2(l, n), _)) = t.first() {
         *morphemes -= 1;
         step(t);
-        let word = word(t, morphemes);
+        let word = word(t, morphemes)?;
         for i in l.iter().rev() { val = Aav1(i.c(), bx(val)); }
-        return Some((Verb, Aav2( bx(val), n.c(), bx(word.map_or(NAN, |x| x.1)) )))
+        return Ok(Some((Verb, Aav2( bx(val), n.c(), bx(word.map_or(NAN, |x| x.1)) ))))
+    }}
+    Ok(Some((rol, val)))
+}
+
+// the items of a pattern up to its `)`. `open` is where it starts
+fn pattern(t: &mut&[Spanned], open: Span) -> Result<Pat, ParseError> {
+    let err = |span, message: &str| Err(ParseError { span, message: String::from(message) });
+    let mut l = Vec::new();
+    loop { match t.first() {
+        Some((Tok::VNoun(v), _)) => { step(t); l.push(Pat::Name(v.c())); },
+        Some((Just(b!('■')), _)) => { step(t); l.push(Pat::Skip); },
+        Some((Just(b'('), span)) => { let span = *span; step(t); l.push(pattern(t, span)?); },
+        Some((Tok::VVerb(v), span)) if v[..] == b","[..] => {
+            if l.iter().any(|x| matches!(x, Pat::Rest(_))) { return err(*span, "more than one rest in a pattern") }
+            step(t);
+            match t.first().map(|x| &x.0) {
+                Some(Tok::VNoun(v)) => { step(t); l.push(Pat::Rest(Some(v.c()))); },
+                Some(Just(b!('■'))) => { step(t); l.push(Pat::Rest(None)); },
+                _ => l.push(Pat::Rest(None)),
+            }
+        },
+        Some((Just(b')'), _)) => { step(t); return Ok(Pat::List(l)) },
+        Some((_, span)) => return err(*span, "unexpected token in pattern"),
+        None => return err(open, "unclosed pattern"),
     }}
-    Some((rol, val))
 }
 
-pub fn phrase_by_morphemes(t: &mut&[Tok], mut morphemes: usize) -> Vec<(Role, Expr)> {
+pub fn phrase_by_morphemes(t: &mut&[Spanned], mut morphemes: usize) -> Result<Vec<(Role, Expr, Span)>, ParseError> {
     let mut phrase = Vec::new();
     let morphemes = &mut morphemes;
     loop {
-        let Some(word) = word(t, morphemes) else { break };
-        phrase.push(word);
+        let before = *t;
+        let Some((rol, word)) = word(t, morphemes)? else { break };
+        phrase.push((rol, word, consumed(before, t)));
         if *morphemes >= 1 { *morphemes -= 1; } else { break };
     };
-    phrase
+    Ok(phrase)
 }
 
-pub fn phrase(t: &mut&[Tok]) -> Vec<(Role, Expr)> {
+pub fn phrase(t: &mut&[Spanned]) -> Result<Vec<(Role, Expr, Span)>, ParseError> {
     let mut phrase = Vec::new();
-    while let Some(word) = word_full(t) { phrase.push(word); }
-    phrase
+    let mut before = *t;
+    while let Some((rol, word)) = word_full(t)? {
+        phrase.push((rol, word, consumed(before, t)));
+        before = *t;
+    }
+    Ok(phrase)
 }
 
 // takes from iterator to make a strand. if it's only one element, it's just the one value. 
 // if it's not a strand, returns None
-fn strand(iter: &mut iter::Peekable<alloc::vec::IntoIter<(Role, Expr)>>) -> Option<Expr> {
+fn strand(iter: &mut iter::Peekable<alloc::vec::IntoIter<(Role, Expr, Span)>>) -> Option<Expr> {
     let mut evs = Vec::new();
-    while let Some((Noun, v)) = iter.peek() {
+    while let Some((Noun, v, _)) = iter.peek() {
         evs.push(v.c()); iter.next();
     }
     (!evs.is_empty()).then(|| if evs.len() == 1 { evs[0].c() } else { Snd(evs) })
 }
 
 
-fn phrase_to_expr(things: Vec<(Role, Expr)>) -> Option<Expr> {
+fn phrase_to_expr(things: Vec<(Role, Expr, Span)>) -> Option<Expr> {
     let mut iter = things.into_iter().peekable();
     Some(if let Some(start) = strand(&mut iter) { // Function application
         let mut value = start;
-        while let Some((Verb, ef)) = iter.next() { value = match strand(&mut iter) {
-            Some(ev) => Afn2(bx(value), bx(ef), bx(ev)),
-            None     => Afn1(bx(value), bx(ef)),
+        while let Some((Verb, ef, span)) = iter.next() { value = match strand(&mut iter) {
+            Some(ev) => Afn2(bx(value), bx(ef), bx(ev), span),
+            None     => Afn1(bx(value), bx(ef), span),
         }}
-    value } else if let Some((Verb, f)) = iter.next() { // Train
+    value } else if let Some((Verb, f, _)) = iter.next() { // Train
         let mut value = if let Some(b) = strand(&mut iter) { Bind(bx(f), bx(b)) } else { f };
-        while let Some((Verb, f)) = iter.next() { value = match strand(&mut iter) {
+        while let Some((Verb, f, _)) = iter.next() { value = match strand(&mut iter) {
             Some(b) => Trn3(bx(value), bx(f), bx(b)),
             None    => Trn2(bx(value), bx(f))
         }}
@@ -231,10 +323,11 @@ fn value_token(chr: Tok) -> Option<Expr> {
 impl Expr {
 fn capture(&self, vars: &mut HashSet<Bstr>) { match self { // yeah...
     Var(n) | SetVar(n) | MutVar(n) => { vars.insert(n.c()); },
+    SetPat(p) => p.capture(vars),
     Int(_) | Flt(_) => (),
     Snd(l) => for i in l { i.capture(vars) }
-    Afn1(a, f)    => { a.capture(vars); f.capture(vars); },
-    Afn2(a, f, b) => { a.capture(vars); f.capture(vars); b.capture(vars); },
+    Afn1(a, f, _)    => { a.capture(vars); f.capture(vars); },
+    Afn2(a, f, b, _) => { a.capture(vars); f.capture(vars); b.capture(vars); },
     Aav1(v, g)    => { vars.insert(v.c()); g.capture(vars); },
     Aav2(f, v, g) => { vars.insert(v.c()); f.capture(vars); g.capture(vars); },
     Bind(f, b)    => { f.capture(vars); b.capture(vars); },
@@ -243,58 +336,80 @@ fn capture(&self, vars: &mut HashSet<Bstr>) { match self { // yeah...
     Fork(a, f, b) => { a.capture(vars); f.capture(vars); b.capture(vars); },
     Dfn { cap, .. } => { vars.extend(cap.iter().cloned()); }
     Block(s) => { for i in s { i.capture(vars) } }
+    Dict(e) => e.capture(vars),
 }}
 }
 
 impl Stmt {
 fn capture(&self, vars: &mut HashSet<Bstr>) { match self {
-    Self::Discard(e) | Self::Return(e) | Self::Loc(e, _) | Self::Mut(e, _) => {
+    Self::Discard(e) | Self::Return(e) | Self::Loc(e, _) | Self::Mut(e, _, _) => {
         e.capture(vars); },
     Self::DelLoc(_) | Self::DelMut(_) => { },
-    Self::Cond(i, t) => { i.capture(vars); t.capture(vars); },
+    Self::LocPat(e, p) => { e.capture(vars); p.capture(vars); },
+    Self::Cond(i, t, _) => { i.capture(vars); t.capture(vars); },
 }}
 }
 
-fn parse_stmt(t: &mut&[Tok], expr: Option<Expr>) -> Option<Stmt> {
-    Some(match t.first() {
+impl Pat {
+fn capture(&self, vars: &mut HashSet<Bstr>) { match self {
+    Self::Name(n) | Self::Rest(Some(n)) => { vars.insert(n.c()); },
+    Self::Skip | Self::Rest(None) => (),
+    Self::List(l) => for i in l { i.capture(vars) },
+}}
+
+/// every name the pattern binds
+pub fn names(&self) -> Vec<&Bstr> { match self {
+    Self::Name(n) | Self::Rest(Some(n)) => vec![n],
+    Self::Skip | Self::Rest(None) => vec![],
+    Self::List(l) => l.iter().flat_map(Pat::names).collect(),
+}}
+}
+
+// `before` is where the statement starts
+fn parse_stmt(t: &mut&[Spanned], expr: Option<Expr>, before: &[Spanned]) -> Result<Option<Stmt>, ParseError> {
+    Ok(Some(match t.first().map(|x| &x.0) {
+        Some(Tok::PSetS) => { let open = t[0].1; step(t); Stmt::LocPat(expr.unwrap_or(NAN), pattern(t, open)?) },
         Some(Tok::VSetS(v)) => { step(t); match expr {
             Some(e) => Stmt::Loc(e, v.c()),
             None    => Stmt::DelLoc(v.c()),
         }},
         Some(Tok::VMutS(v)) => { step(t); match expr {
-            Some(e) => Stmt::Mut(e, v.c()),
+            Some(e) => Stmt::Mut(e, v.c(), consumed(before, t)),
             None    => Stmt::DelMut(v.c()),
         }},
         Some(Just(b!('◘'))) => { step(t); Stmt::Return (expr.unwrap_or(NAN)) },
         Some(Just(b!('·'))) => { step(t); Stmt::Discard(expr.unwrap_or(NAN)) },
         Some(Just(b!('}'']'))) | None => { Stmt::Return(expr.unwrap_or(NAN)) },
         Some(Just(b!('?'))) => { step(t);
-            let ev2 = phrase_to_expr(phrase(t));
-            Stmt::Cond(expr.unwrap_or(NAN), bx(parse_stmt(t, ev2)?))
+            let span = consumed(before, t);
+            let then = *t;
+            let ev2 = phrase_to_expr(phrase(t)?);
+            let Some(then) = parse_stmt(t, ev2, then)? else { return Ok(None) };
+            Stmt::Cond(expr.unwrap_or(NAN), bx(then), span)
         },
-        _ => return None,
-    })
+        _ => return Ok(None),
+    }))
 }
 
-pub fn block(t: &mut&[Tok]) -> Vec<Stmt> {
+pub fn block(t: &mut&[Spanned]) -> Result<Vec<Stmt>, ParseError> {
     let mut exps = Vec::new();
     loop {
-        while let Some(Just(b!('·'))) = t.first() { step(t); }
-        if let Some(Just(b!('}'']'))) | None = t.first() { break };
-        let ev = phrase_to_expr(phrase(t));
-        if let Some(stmt) = parse_stmt(t, ev) {
+        while let Some((Just(b!('·')), _)) = t.first() { step(t); }
+        if let Some((Just(b!('}'']')), _)) | None = t.first() { break };
+        let before = *t;
+        let ev = phrase_to_expr(phrase(t)?);
+        if let Some(stmt) = parse_stmt(t, ev, before)? {
             exps.push(stmt);
         } else { break }
     }
-    exps
+    Ok(exps)
 }
 
-pub fn parse(t: &[Tok]) -> Vec<Stmt> {
+pub fn parse(t: &[Spanned]) -> Result<Vec<Stmt>, ParseError> {
     let mut slice = t;
-    let exps = block(&mut slice);
-    if !slice.is_empty() {
-        let num = t.len() - slice.len();
-        panic!("unexpected token {:?} at {}", t[num], num);
+    let exps = block(&mut slice)?;
+    if let Some((_, span)) = slice.first() {
+        return Err(ParseError { span: *span, message: String::from("unexpected token") });
     }
-    exps
+    Ok(exps)
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use super::{Val, Env, NAN};
+use super::{Val::{self, Int}, Env, NAN, List, Dict, array, inverse, Error, ErrorKind, Fallible, list::{self, collect_ok}};
 use crate::prelude::*;
 
 pub type AvT = fn(&mut Env, Val, Option<Val>, Option<&Rc<Val>>, &Rc<Val>) -> Val;
@@ -23,19 +23,22 @@ adverb!(@env, a .toleft g _b => g.monad(env, a) );
 adverb!(@env, a .toright g b => g.monad(env, b.unwrap_or(a)) );
 adverb!(@env, a f .valences g b => (if b.is_none() {f} else {g}).call(env, a, b));
 adverb!(@env, a f .over g b => {
-    let l = f.monad(env, a);
-    let r = b.map(|b| f.monad(env, b));
+    let l = ok!(f.monad(env, a));
+    let r = ok!(b.map(|b| f.monad(env, b).into_result()).transpose());
     g.call(env, l, r)
 });
 adverb!(@env, a f .bind g _b => g.dyad(env, a, (**f).c()));
-adverb!(@env, a f .atop g b  => { let x = f.call(env, a, b); g.monad(env, x) });
-adverb!(@env, a f .overleft  g b => { let l = f.monad(env, a); g.call(env, l, b) });
-adverb!(@env, a f .overright g b => { let r = b.map(|b| f.monad(env, b)); g.call(env, a, r) });
-adverb!(@env, a f .forkleft  g b => { let l = f.call(env, a.c(), b.c()); g.dyad(env, l, b.unwrap_or(a)) });
-adverb!(@env, a f .forkright g b => { let r = f.call(env, a.c(), b); g.dyad(env, a, r) });
+adverb!(@env, a f .atop g b  => { let x = ok!(f.call(env, a, b)); g.monad(env, x) });
+adverb!(@env, a f .overleft  g b => { let l = ok!(f.monad(env, a)); g.call(env, l, b) });
+adverb!(@env, a f .overright g b => {
+    let r = ok!(b.map(|b| f.monad(env, b).into_result()).transpose()); g.call(env, a, r)
+});
+adverb!(@env, a f .forkleft  g b => { let l = ok!(f.call(env, a.c(), b.c())); g.dyad(env, l, b.unwrap_or(a)) });
+adverb!(@env, a f .forkright g b => { let r = ok!(f.call(env, a.c(), b)); g.dyad(env, a, r) });
 
 adverb!(@env, a .each g b => {
     let Some(b) = b else { return eachleft(env, a, None, None, g); };
+    if let Some(x) = each_dict(env, &a, &b, |env, x, y| g.dyad(env, x, y)) { return x }
     if a.is_infinite() || b.is_infinite() {
         return Val::Fork(a.rc(), g.c(), b.rc());
     }
@@ -45,25 +48,48 @@ adverb!(@env, a .each g b => {
         ( true, false) => b.len(),
         (false, false) => usize::max(a.len(), b.len()),
     }).map(|n| {
-        let l = a.index(env, n); let r = b.index(env, n); g.dyad(env, l, r)
-    }).collect();
+        let l = ok!(a.index(env, n)); let r = ok!(b.index(env, n)); g.dyad(env, l, r)
+    });
+    let items = ok!(collect_ok(items));
     let mut fill = NAN;
-    if b.is_scalar() && !a.fill().is_nan() { fill = g.dyad(env, a.fill(), b.c()); }
-    if a.is_scalar() && !b.fill().is_nan() { fill = g.dyad(env, a.c(), b.fill()); }
-    if !a.fill().is_nan() && !b.fill().is_nan() { fill = g.dyad(env, a.fill(), b.fill()); };
+    if b.is_scalar() && !a.fill().is_nan() { fill = ok!(g.dyad(env, a.fill(), b.c())); }
+    if a.is_scalar() && !b.fill().is_nan() { fill = ok!(g.dyad(env, a.c(), b.fill())); }
+    if !a.fill().is_nan() && !b.fill().is_nan() { fill = ok!(g.dyad(env, a.fill(), b.fill())); };
     Val::lis_fill(items, fill)
 });
 
+// call `f` with the values of dictionaries, keeping their keys. values with the same key are
+// paired, the rest are left out
+fn each_dict(env: &mut Env, a: &Val, b: &Val, mut f: impl FnMut(&mut Env, Val, Val) -> Val) -> Option<Val> {
+    let values = |d: &Dict, x: Result<Vec<Val>, Val>| x.map_or_else(|e| e, |x| d.with_values(x).into());
+    Some(match (a, b) {
+        (Val::Dict(l), Val::Dict(r)) => {
+            let mut dict = Dict::default();
+            for (k, x) in l.iter() { if let Some(y) = r.get(k) {
+                let x = f(env, x.c(), y.c());
+                if x.is_err() { return Some(x) }
+                dict.insert(k.c(), x);
+            }}
+            dict.into()
+        },
+        (Val::Dict(d), _) => values(d, collect_ok(d.values().iter().map(|x| f(env, x.c(), b.c())))),
+        (_, Val::Dict(d)) => values(d, collect_ok(d.values().iter().map(|x| f(env, a.c(), x.c())))),
+        _ => return None,
+    })
+}
+
 adverb!(@env, a .eachleft g b =>
     if a.is_scalar() {
         g.call(env, a, b)
+    } else if let Val::Dict(d) = &a {
+        d.with_values(ok!(collect_ok(d.values().iter().map(|x| g.call(env, x.c(), b.c()))))).into()
     } else if a.is_infinite() { match b {
         Some(b) => Val::Fork(a.rc(), Rc::clone(g), b.rc()),
         None    => Val::atop(a.rc(), Rc::clone(g)),
     }} else {
         Val::lis_fill(
-            a.iterf().map(|x| g.call(env, x.c(), b.c())).collect(),
-            if a.fill().is_nan() { NAN } else { g.call(env, a.fill(), b.c()) }
+            ok!(collect_ok(a.iterf().map(|x| g.call(env, x.c(), b.c())))),
+            if a.fill().is_nan() { NAN } else { ok!(g.call(env, a.fill(), b.c())) }
         )
     }
 );
@@ -79,56 +105,67 @@ adverb!(@env, a .eachtrim g b => {
         ( true, false) => b.len(),
         (false, false) => usize::min(a.len(), b.len()),
     }).map(|n| {
-        let l = a.index(env, n); let r = b.index(env, n); g.dyad(env, l, r)
+        let l = ok!(a.index(env, n)); let r = ok!(b.index(env, n)); g.dyad(env, l, r)
     }).collect()
 });
 
 adverb!(@env, a .conform g b => {
     let Some(b) = b else { return extend(env, a, None, None, g); };
+    if let Some(x) = each_dict(env, &a, &b, |env, x, y| conform(env, x, Some(y), None, g)) { return x }
+    if let Val::Func(f) = **g { if let Some(x) = array::conform(env, f, &a, &b) { return x } }
     if a.is_infinite() || b.is_infinite() {
         return Val::Fork(a.rc(), Val::Av(conform, None, g.c()).rc(), b.rc());
     }
+    // going into nested lists counts as a call, so very nested ones can't overflow the stack
+    env.deeper(|env| {
     let items = (0..match (a.is_scalar(), b.is_scalar()) {
         ( true,  true) => return g.call(env, a, Some(b)),
         (false,  true) => a.len(),
         ( true, false) => b.len(),
         (false, false) => usize::max(a.len(), b.len()),
     }).map(|n| {
-        let l = a.index(env, n); let r = b.index(env, n); conform(env, l, Some(r), None, g)
-    }).collect();
+        let l = ok!(a.index(env, n)); let r = ok!(b.index(env, n)); conform(env, l, Some(r), None, g)
+    });
+    let items = ok!(collect_ok(items));
     let mut fill = NAN;
-    if b.is_scalar() && !a.fill().is_nan() { fill = conform(env, a.fill(), Some(b.c()), None, g); }
-    if a.is_scalar() && !b.fill().is_nan() { fill = conform(env, a.c(), Some(b.fill()), None, g); }
+    if b.is_scalar() && !a.fill().is_nan() { fill = ok!(conform(env, a.fill(), Some(b.c()), None, g)); }
+    if a.is_scalar() && !b.fill().is_nan() { fill = ok!(conform(env, a.c(), Some(b.fill()), None, g)); }
     if !a.fill().is_nan() && !b.fill().is_nan() { 
-        fill = conform(env, a.fill(), Some(b.fill()), None, g);
+        fill = ok!(conform(env, a.fill(), Some(b.fill()), None, g));
     };
     Val::lis_fill(items, fill)
+    })
 });
 
 adverb!(@env, a .extend g b =>
-    if a.is_scalar() {
+    if let Some(x) = match **g { Val::Func(f) => array::extend(env, f, &a, b.as_ref()), _ => None } {
+        x
+    } else if a.is_scalar() {
         g.call(env, a, b)
+    } else if let Val::Dict(d) = &a {
+        d.with_values(ok!(collect_ok(d.values().iter().map(|x| extend(env, x.c(), b.c(), None, g))))).into()
     } else if a.is_infinite() { match b {
         Some(b) => Val::Fork(a.rc(), Val::Av(conform, None, Rc::clone(g)).rc(), b.rc()),
         None    => Val::atop(a.rc(), Val::Av(conform, None, Rc::clone(g)).rc()),
-    }} else {
-        let fill = if a.fill().is_nan() { NAN } else { extend(env, a.fill(), b.c(), None, g) };
-        Val::lis_fill(a.into_iterf().map(|x| extend(env, x, b.c(), None, g)).collect(), fill)
-    }
+    }} else { env.deeper(|env| {
+        let fill = if a.fill().is_nan() { NAN } else { ok!(extend(env, a.fill(), b.c(), None, g)) };
+        Val::lis_fill(ok!(collect_ok(a.into_iterf().map(|x| extend(env, x, b.c(), None, g)))), fill)
+    })}
 );
 
 adverb!(@env, a .scan g b => {
     if a.is_infinite() { return NAN; }
+    if let Some(x) = array::fold(g, &a, b.as_ref(), true) { return x }
     let mut values = Vec::with_capacity(a.len());
     let mut iter = a.into_iterf();
     let Some(start) = iter.next() else { return b.unwrap_or(NAN); };
     let mut val = match b {
-        Some(b) => g.dyad(env, b, start),
+        Some(b) => ok!(g.dyad(env, b, start)),
         None => start,
     };
     values.push(val.c());
     for i in iter {
-        val = g.dyad(env, val, i);
+        val = ok!(g.dyad(env, val, i));
         values.push(val.c());
     }
     Val::lis(values)
@@ -136,21 +173,22 @@ adverb!(@env, a .scan g b => {
 
 adverb!(@env, a .reduce g b => {
     if a.is_infinite() { return NAN; }
+    if let Some(x) = array::fold(g, &a, b.as_ref(), false) { return x }
     let mut iter = a.into_iterf();
     let Some(start) = iter.next() else { return b.unwrap_or(NAN); };
     let mut val = match b {
-        Some(b) => g.dyad(env, b, start),
+        Some(b) => ok!(g.dyad(env, b, start)),
         None => start,
     };
-    for i in iter { val = g.dyad(env, val, i); }
+    for i in iter { val = ok!(g.dyad(env, val, i)); }
     val
 });
 
 adverb!(@env, a f .untilscan g b => {
     let mut values = vec![a.c()];
     let mut val = a;
-    while !f.monad(env, val.c()).as_bool() {
-        val = g.call(env, val.c(), b.c());
+    while !ok!(f.monad(env, val.c())).as_bool() {
+        val = ok!(g.call(env, val.c(), b.c()));
         values.push(val.c());
     }
     Val::lis(values)
@@ -158,8 +196,8 @@ adverb!(@env, a f .untilscan g b => {
 
 adverb!(@env, a f .until g b => {
     let mut val = a;
-    while !f.monad(env, val.c()).as_bool() {
-        val = g.call(env, val.c(), b.c());
+    while !ok!(f.monad(env, val.c())).as_bool() {
+        val = ok!(g.call(env, val.c(), b.c()));
     }
     val
 });
@@ -168,8 +206,8 @@ adverb!(@env, a f .untilscancmp g b => {
     let mut values = vec![a.c()];
     let mut val = a;
     loop {
-        let tried = g.call(env, val.c(), b.c());
-        if f.dyad(env, tried.c(), val).as_bool() { break }
+        let tried = ok!(g.call(env, val.c(), b.c()));
+        if ok!(f.dyad(env, tried.c(), val)).as_bool() { break }
         values.push(tried.c());
         val = tried;
     }
@@ -179,58 +217,108 @@ adverb!(@env, a f .untilscancmp g b => {
 adverb!(@env, a f .untilcmp g b => {
     let mut val = a;
     loop {
-        let tried = g.call(env, val.c(), b.c());
-        if f.dyad(env, tried.c(), val.c()).as_bool() { break }
+        let tried = ok!(g.call(env, val.c(), b.c()));
+        if ok!(f.dyad(env, tried.c(), val.c())).as_bool() { break }
         val = tried;
     }
     val
 });
 
 adverb!(@env, a f .powerscan g b => {
-    let num = f.call(env, a.c(), b.c()).try_int().map_or(0, |x| x.try_into().unwrap_or(0));
+    let num = ok!(f.call(env, a.c(), b.c()));
+    let (num, g) = ok!(power_count(env, num, g));
     let mut values = Vec::with_capacity(num);
     values.push(a.c());
     let mut val = a;
     for _ in 0..num {
-        val = g.call(env, val, b.c());
+        val = ok!(g.call(env, val, b.c()));
         values.push(val.c());
     }
     Val::lis(values)
 });
 
 adverb!(@env, a f .power g b => {
-    let num = f.call(env, a.c(), b.c()).try_int().map_or(0, |x| x.try_into().unwrap_or(0));
+    let num = ok!(f.call(env, a.c(), b.c()));
+    let (num, g) = ok!(power_count(env, num, g));
     let mut val = a;
     for _ in 0..num {
-        val = g.call(env, val, b.c());
+        val = ok!(g.call(env, val, b.c()));
     }
     val
 });
 
+// negative counts run the inverse
+fn power_count(env: &Env, n: Val, g: &Rc<Val>) -> Result<(usize, Rc<Val>), Val> {
+    let n = n.try_int().unwrap_or(0);
+    let g = if n < 0 { inverse::inverse_or_err(env, g, "╨")? } else { g.c() };
+    Ok((n.unsigned_abs().try_into().unwrap_or(usize::MAX), g))
+}
+
 adverb!(@env, a .scanpairs g b => {
     if a.len() == 0 { return Val::lis_fill(Vec::new(), a.fill()); }
     let elems = a.iterf().collect::<Vec<_>>();
     let mut list = Vec::with_capacity(elems.len());
-    let first = if let Some(b) = b { g.dyad(env, b, elems[0].c()) } else { elems[0].c() };
+    let first = if let Some(b) = b { ok!(g.dyad(env, b, elems[0].c())) } else { elems[0].c() };
     list.push(first);
-    for i in 1..elems.len() { list.push(g.dyad(env, elems[i-1].c(), elems[i].c())); }
+    for i in 1..elems.len() { list.push(ok!(g.dyad(env, elems[i-1].c(), elems[i].c()))); }
     Val::lis(list)
 });
 
 adverb!(@env, a f .stencil g b => {
-    if let Some(size) = f.call(env, a.c(), b.c()).try_int().map(|x| x as usize) {
-        if a.is_infinite() { return Val::lis(Vec::new()); }
+    let size = ok!(f.call(env, a.c(), b.c()));
+    if a.is_infinite() { return Val::lis(Vec::new()); }
+    if let Some(size) = size.try_int().map(|x| x as usize) {
         (0..(a.len() + 1).saturating_sub(size)).map(|n| {
             g.call(env, a.iterf().skip(n).take(size).cloned().collect(), b.c())
         }).collect()
     } else {
-        // we could do something smart here like using multiple dimensions but uh
-        Val::lis(Vec::new())
+        // a list of sizes, or a list of sizes and a list of steps
+        let ints = |x: &Val| x.iterf().map(|x| x.try_int().map_or(0, |x| x.max(0) as usize)).collect::<Vec<_>>();
+        let (size, step) = match size.iterf().next() {
+            Some(x) if x.is_list() => (ints(x), ints(&ok!(size.index(env, 1)))),
+            _ => (ints(&size), Vec::new()),
+        };
+        let step = (0..size.len()).map(|n| step.get(n).copied().unwrap_or(1).max(1)).collect::<Vec<_>>();
+        ok!(env.check_alloc(size.iter().try_fold(1usize, |x, &y| x.checked_mul(y)).unwrap_or(usize::MAX)));
+        let mut shape = list::ishape(&a, size.len() as i64 - 1);
+        shape.resize(size.len(), 1);
+        let c = Stencil { size, step, shape, g, b };
+        c.windows(env, &a, &mut Vec::new())
     }
 });
 
+// windows centered on every `step`th item, padded with the fill of the innermost list that has one
+struct Stencil<'a> { size: Vec<usize>, step: Vec<usize>, shape: Vec<usize>, g: &'a Rc<Val>, b: Option<Val> }
+
+impl Stencil<'_> {
+    fn windows(&self, env: &mut Env, a: &Val, start: &mut Vec<i64>) -> Val {
+        let d = start.len();
+        if d == self.size.len() {
+            let w = ok!(window(env, a, start, &self.size, NAN));
+            return self.g.call(env, w, self.b.c())
+        }
+        let items = (0..self.shape[d].div_ceil(self.step[d])).map(|i| {
+            start.push((i * self.step[d]) as i64 - (self.size[d] as i64 - 1) / 2);
+            let x = self.windows(env, a, start);
+            start.pop();
+            x
+        });
+        Val::lis(ok!(collect_ok(items)))
+    }
+}
+
+fn window(env: &mut Env, a: &Val, start: &[i64], size: &[usize], fill: Val) -> Result<Val, Val> {
+    let (Some(&s), Some(&k)) = (start.first(), size.first()) else { return Ok(a.c()) };
+    let fill = if a.is_list() && !a.fill().is_nan() { a.fill() } else { fill };
+    let items = (s..s + k as i64).map(|n| {
+        let x = if n < 0 || n as usize >= a.len() && a.is_list() { fill.c() } else { ok!(a.index(env, n as usize)) };
+        window(env, &x, &start[1..], &size[1..], fill.c()).unwrap_or_else(|e| e)
+    });
+    Ok(Val::lis(collect_ok(items)?))
+}
+
 adverb!(@env, a f .drill g b => {
-    let iter = (**f).call(env, a.c(), b.c()).into_iterf();
+    let iter = ok!((**f).call(env, a.c(), b.c())).into_iterf();
     drill_iter(env, a, b, iter, g)
 });
 
@@ -246,20 +334,59 @@ pub fn drill_iter(
     if index.is_nan() { return a; }
     let Some(index) = index.try_int().and_then(|x| usize::try_from(x).ok()) else {return a};
     let Val::Lis{l, fill} = a else { return g.call(env, a, b); };
-    let mut v = match Rc::try_unwrap(l) { Ok(l) => l, Err(l) => l.to_vec(), };
+    let mut v = List::unwrap(l);
     if v.len() <= index { v.resize(index+1, (*fill).c()); }
-    v[index] = drill_iter(env, core::mem::take(&mut v[index]), b, iter, g);
+    v[index] = ok!(drill_iter(env, core::mem::take(&mut v[index]), b, iter, g));
     Val::lis_fill(v, (*fill).c())
 }
 
+adverb!(@env, a f .rank g b => {
+    let r = ok!(f.call(env, a.c(), b.c()));
+    let mut iter = r.iterf().map(Val::try_int);
+    let Some(Some(ra)) = iter.next() else { return NAN };
+    let Some(rb) = iter.last().unwrap_or(Some(ra)) else { return NAN };
+    rank_cells(env, a, b, ra, rb, g)
+});
+
+// how deeply a value is nested: 0 for scalars, 1 for a list of scalars
+fn depth(a: &Val) -> usize {
+    if a.is_list() { 1 + a.iterf().map(depth).max().unwrap_or(0) } else { 0 }
+}
+
+// whether the cells of rank `r` are inside `a`. negative ranks count from the leaves
+fn goes_in(a: &Val, r: i64) -> bool {
+    a.is_list() && if r >= 0 { r > 0 } else { depth(a) as i64 > -r }
+}
+
+fn rank_cells(env: &mut Env, a: Val, b: Option<Val>, ra: i64, rb: i64, g: &Rc<Val>) -> Val {
+    let (ina, inb) = (goes_in(&a, ra), b.as_ref().is_some_and(|b| goes_in(b, rb)));
+    if !ina && !inb { return g.call(env, a, b) }
+    let next = |r: i64, goes: bool| if goes && r > 0 { r - 1 } else { r };
+    let (na, nb) = (next(ra, ina), next(rb, inb));
+    let len = match (ina, &b) {
+        (true, Some(b)) if inb => usize::max(a.len(), b.len()),
+        (true, _) => a.len(),
+        (false, b) => b.as_ref().map_or(0, Val::len),
+    };
+    let items = (0..len).map(|n| {
+        let l = if ina { ok!(a.index(env, n)) } else { a.c() };
+        let r = match &b { Some(b) if inb => Some(ok!(b.index(env, n))), b => b.c() };
+        rank_cells(env, l, r, na, nb, g)
+    });
+    let items = ok!(collect_ok(items));
+    let (fa, fb) = (if ina { a.fill() } else { a.c() }, b.as_ref().map(|b| if inb { b.fill() } else { b.c() }));
+    let fill = if fa.is_nan() || fb.as_ref().is_some_and(Val::is_nan) { NAN } else { ok!(rank_cells(env, fa, fb, na, nb, g)) };
+    Val::lis_fill(items, fill)
+}
+
 adverb!(@env, a f .amend g b => {
-    let Some(indices) = f.call(env, a.c(), b.c()).iterf()
+    let Some(indices) = ok!(f.call(env, a.c(), b.c())).iterf()
         .map(|x| x.try_int().and_then(|x| usize::try_from(x).ok()))
         .collect::<Option<Vec<usize>>>() else {return a};
     let Val::Lis{l, fill} = a else { return a; };
-    let mut l = match Rc::try_unwrap(l) { Ok(l) => l, Err(l) => l.to_vec(), };
+    let mut l = List::unwrap(l);
     let before = indices.iter().copied().map(|i| l.get(i).unwrap_or(&fill).c()).collect::<Val>();
-    let after = g.call(env, before, b);
+    let after = ok!(g.call(env, before, b));
     if !after.is_list() {
         let max = indices.iter().max();
         if let Some(&max) = max { if l.len() <= max { l.resize(max, (*fill).c()); }}
@@ -279,7 +406,128 @@ adverb!(@env, a f .amend g b => {
         let mut iter = 0..; 
         l.retain(|_| !to_be_removed.contains(&iter.next().unwrap()));
     }
-    Val::Lis { l: Rc::new(l), fill }
+    Val::Lis { l: Rc::new(l.into()), fill }
+});
+
+adverb!(@env, a f .under g b => {
+    // the leaves of α are swapped for markers, so F's result says where each item came from
+    let mut leaves = Vec::new();
+    let marked = mark(&a, &mut leaves);
+    let at = ok!(f.call(env, marked, b.c()));
+    let before = ok!(f.call(env, a.c(), b.c()));
+    let after = ok!(g.call(env, before.c(), b));
+    let mut new = HashMap::new();
+    if let Err(e) = unmark(&at, &before, after, &leaves, &mut new) { return e }
+    remark(a, &mut 0, &mut new)
+});
+
+const MARK: i64 = 1 << 53;
+
+fn mark(a: &Val, leaves: &mut Vec<Val>) -> Val { match a {
+    Val::Lis { .. } => a.iterf().map(|x| mark(x, leaves)).collect(),
+    Val::Dict(d) => d.with_values(d.values().iter().map(|x| mark(x, leaves)).collect()).into(),
+    a => { leaves.push(a.c()); Int(MARK + leaves.len() as i64 - 1) },
+}}
+
+// find where the items of `after` go. `before` is what F gave for the real α
+fn unmark(at: &Val, before: &Val, after: Val, leaves: &[Val], new: &mut HashMap<usize, Val>) -> Result<(), Val> {
+    let fail = |msg: &str| Err(Error::new(ErrorKind::Inverse, msg).prim("_╔").into());
+    match at {
+        Int(m) if (MARK..MARK + leaves.len() as i64).contains(m) => {
+            let old = &leaves[(m - MARK) as usize];
+            if old != before && !(old.is_nan() && before.is_nan()) { return fail("selector isn't structural") }
+            if new.insert((m - MARK) as usize, after).is_some() { return fail("selector uses an item twice") }
+        },
+        // fills don't come from α
+        x if x.is_nan() => {},
+        Val::Lis { .. } | Val::Dict(_) => {
+            if at.len() != before.len() || !(before.is_list() || matches!(before, Val::Dict(_))) {
+                return fail("selector isn't structural")
+            }
+            let after = if after.is_scalar() { iter::repeat_n(after, at.len()).collect() } else { after };
+            if after.len() != at.len() { return fail("modified selection changed shape") }
+            for ((at, before), after) in at.iterf().zip(before.iterf()).zip(after.into_iterf()) {
+                unmark(at, before, after, leaves, new)?;
+            }
+        },
+        _ => return fail("selector isn't structural"),
+    }
+    Ok(())
+}
+
+fn remark(a: Val, n: &mut usize, new: &mut HashMap<usize, Val>) -> Val { match a {
+    Val::Lis { l, fill } => {
+        let l = List::unwrap(l).into_iter().map(|x| remark(x, n, new)).collect::<Vec<_>>();
+        Val::Lis { l: Rc::new(l.into()), fill }
+    },
+    Val::Dict(d) => {
+        let vals = d.values().iter().map(|x| remark(x.c(), n, new)).collect();
+        d.with_values(vals).into()
+    },
+    a => { *n += 1; new.remove(&(*n - 1)).unwrap_or(a) },
+}}
+
+adverb!(@env, a .undo g b => {
+    let inv = ok!(inverse::inverse_or_err(env, g, "_┴"));
+    inv.call(env, a, b)
+});
+
+adverb!(@env, a .sortby g b => { let order = ok!(key_order(env, &a, b, g)); pick(&a, &order) });
+adverb!(@env, a .gradeby g b => { let order = ok!(key_order(env, &a, b, g)); grade(order) });
+adverb!(@env, a .sortwith g _b => { let order = ok!(cmp_order(env, &a, g)); pick(&a, &order) });
+adverb!(@env, a .gradewith g _b => { let order = ok!(cmp_order(env, &a, g)); grade(order) });
+
+fn pick(a: &Val, order: &[usize]) -> Val {
+    let items = a.iterf().collect::<Vec<_>>();
+    order.iter().map(|&i| items[i].c()).collect()
+}
+
+fn grade(order: Vec<usize>) -> Val { order.into_iter().map(|i| Int(i as i64)).collect() }
+
+// the order that sorts the items of α by their keys `xGβ`, computing each key once
+fn key_order(env: &mut Env, a: &Val, b: Option<Val>, g: &Rc<Val>) -> Result<Vec<usize>, Val> {
+    let keys = collect_ok(a.iterf().map(|x| g.call(env, x.c(), b.c())))?;
+    let mut order = (0..keys.len()).collect::<Vec<_>>();
+    order.sort_by(|&x, &y| keys[x].cmpval(&keys[y]));
+    Ok(order)
+}
+
+// the order that sorts the items of α, where `xGy` is positive if y goes before x. comparators
+// don't have to be consistent, so this is a merge sort instead of `sort_by`, which could panic
+fn cmp_order(env: &mut Env, a: &Val, g: &Rc<Val>) -> Result<Vec<usize>, Val> {
+    let items = a.iterf().collect::<Vec<_>>();
+    let mut after = |x: usize, y: usize| -> Result<bool, Val> {
+        Ok(g.dyad(env, items[x].c(), items[y].c()).into_result()?.as_c().re > 0.)
+    };
+    let mut order = (0..items.len()).collect::<Vec<_>>();
+    merge_sort(&mut order, &mut Vec::new(), &mut after)?;
+    Ok(order)
+}
+
+fn merge_sort(
+    v: &mut [usize], buf: &mut Vec<usize>, after: &mut impl FnMut(usize, usize) -> Result<bool, Val>
+) -> Result<(), Val> {
+    if v.len() < 2 { return Ok(()) }
+    let mid = v.len() / 2;
+    merge_sort(&mut v[..mid], buf, after)?;
+    merge_sort(&mut v[mid..], buf, after)?;
+    buf.clear();
+    let (mut i, mut j) = (0, mid);
+    while i < mid && j < v.len() {
+        if after(v[i], v[j])? { buf.push(v[j]); j += 1; } else { buf.push(v[i]); i += 1; }
+    }
+    buf.extend_from_slice(&v[i..mid]);
+    buf.extend_from_slice(&v[j..]);
+    v.copy_from_slice(buf);
+    Ok(())
+}
+
+adverb!(@env, a f .catch g b => match g.call(env, a, b) {
+    Val::Err(e) if !matches!(e.kind, ErrorKind::Exit | ErrorKind::Fuel | ErrorKind::Cancelled) => {
+        let arg = e.payload.as_ref().map_or_else(|| e.message.chars().map(|x| Int(x as i64)).collect(), Val::c);
+        f.dyad(env, Int(i64::from(e.code)), arg)
+    },
+    x => x,
 });
 
 adverb!(@env, a .cycle g _b => {
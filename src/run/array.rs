@@ -0,0 +1,260 @@
+use core::{cell::OnceCell, ops::Deref, hash::{Hash, Hasher}};
+use crate::prelude::*;
+use super::{Val::{self, Int, Num, Lis}, Env, Func, c64, func, adverb::{self, AvT}, list::GoodIter};
+
+/// the items of a list. lists of only integers, complex numbers or bytes are stored packed, and
+/// only turned into `Val`s (once) when something needs a `&[Val]`
+pub enum List {
+    Vals(Vec<Val>),
+    Ints(Vec<i64>, OnceCell<Vec<Val>>),
+    Nums(Vec<c64>, OnceCell<Vec<Val>>),
+    Bytes(Vec<u8>, OnceCell<Vec<Val>>),
+}
+use List::{Vals, Ints, Nums, Bytes};
+
+impl List {
+    pub fn ints(v: Vec<i64>) -> List { Ints(v, OnceCell::new()) }
+    pub fn nums(v: Vec<c64>) -> List { Nums(v, OnceCell::new()) }
+    pub fn bytes(v: Vec<u8>) -> List { Bytes(v, OnceCell::new()) }
+
+    // these shadow the slice methods so they don't unpack the list
+    pub fn len(&self) -> usize { match self {
+        Vals(v) => v.len(), Ints(v, _) => v.len(), Nums(v, _) => v.len(), Bytes(v, _) => v.len(),
+    }}
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn item(&self, index: usize) -> Option<Val> { match self {
+        Vals(v) => v.get(index).cloned(),
+        Ints(v, _) => v.get(index).map(|&x| Int(x)),
+        Nums(v, _) => v.get(index).map(|&x| Num(x)),
+        Bytes(v, _) => v.get(index).map(|&x| Int(i64::from(x))),
+    }}
+
+    pub fn is_packed(&self) -> bool { !matches!(self, Vals(_)) }
+
+    /// collect items, keeping them packed if they're all ints or all numbers
+    pub fn pack(iter: impl Iterator<Item=Val>) -> Result<List, Val> {
+        let mut list = List::ints(Vec::with_capacity(iter.size_hint().0));
+        for i in iter {
+            if i.is_err() { return Err(i) }
+            list.push(i);
+        }
+        Ok(list)
+    }
+
+    pub fn push(&mut self, x: Val) { match (&mut *self, x) {
+        (Vals(v), x) => v.push(x),
+        (Ints(v, c), Int(x)) => { v.push(x); c.take(); },
+        (Nums(v, c), Num(x)) => { v.push(x); c.take(); },
+        (Bytes(v, c), Int(x @ 0..=255)) => { v.push(x as u8); c.take(); },
+        (Ints(v, _), Num(x)) if v.is_empty() => *self = List::nums(vec![x]),
+        (_, x) => {
+            let mut v = core::mem::replace(self, Vals(Vec::new())).into_vec();
+            v.push(x);
+            *self = Vals(v);
+        }
+    }}
+
+    /// iterate over owned items without unpacking
+    pub fn items(&self) -> Box<dyn GoodIter<Val> + '_> { match self {
+        Vals(v) => bx(v.iter().cloned()),
+        Ints(v, _) => bx(v.iter().map(|&x| Int(x))),
+        Nums(v, _) => bx(v.iter().map(|&x| Num(x))),
+        Bytes(v, _) => bx(v.iter().map(|&x| Int(i64::from(x)))),
+    }}
+
+    pub fn into_items(self) -> Box<dyn GoodIter<Val>> { match self {
+        Vals(v) => bx(v.into_iter()),
+        Ints(v, _) => bx(v.into_iter().map(Int)),
+        Nums(v, _) => bx(v.into_iter().map(Num)),
+        Bytes(v, _) => bx(v.into_iter().map(|x| Int(i64::from(x)))),
+    }}
+
+    pub fn into_vec(self) -> Vec<Val> { match self {
+        Vals(v) => v,
+        Ints(_, mut c) | Nums(_, mut c) | Bytes(_, mut c) if c.get().is_some() => c.take().unwrap(),
+        x => x.into_items().collect(),
+    }}
+
+    /// get the items out of an rc, cloning only if needed
+    pub fn unwrap(l: Rc<List>) -> Vec<Val> {
+        Rc::try_unwrap(l).map_or_else(|l| l.to_vec(), List::into_vec)
+    }
+}
+
+impl Deref for List {
+    type Target = [Val];
+    fn deref(&self) -> &[Val] { match self {
+        Vals(v) => v,
+        Ints(_, c) | Nums(_, c) | Bytes(_, c) => c.get_or_init(|| self.items().collect()),
+    }}
+}
+
+impl From<Vec<Val>> for List {
+    fn from(v: Vec<Val>) -> List { Vals(v) }
+}
+
+impl From<List> for Val {
+    fn from(l: List) -> Val {
+        ok!(super::check_len(l.len()));
+        Val::Lis { l: Rc::new(l), fill: Rc::new(super::NAN) }
+    }
+}
+
+impl Clone for List {
+    fn clone(&self) -> List { match self {
+        Vals(v) => Vals(v.c()),
+        Ints(v, _) => List::ints(v.c()),
+        Nums(v, _) => List::nums(v.c()),
+        Bytes(v, _) => List::bytes(v.c()),
+    }}
+}
+
+impl PartialEq for List {
+    fn eq(&self, other: &List) -> bool { match (self, other) {
+        (Ints(l, _), Ints(r, _)) => l == r,
+        (Bytes(l, _), Bytes(r, _)) => l == r,
+        (l, r) => l.len() == r.len() && l.items().zip(r.items()).all(|(x, y)| x == y),
+    }}
+}
+
+// hashes like a slice of vals, so packed and unpacked lists hash the same
+impl Hash for List {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for i in self.items() { i.hash(state); }
+    }
+}
+
+// fast paths for pervasive functions. these only work on packed lists with no fill and scalars
+
+fn packed(a: &Val) -> Option<&List> { match a {
+    Lis { l, fill } if l.is_packed() && fill.is_nan() => Some(l),
+    _ => None,
+}}
+
+/// the numbers in a packed list or a scalar
+enum Numbers<'a, T: Clone> { List(Cow<'a, [T]>), Scalar(T) }
+use alloc::borrow::Cow;
+
+fn as_ints(a: &Val) -> Option<Numbers<'_, i64>> { match a {
+    Int(n) => Some(Numbers::Scalar(*n)),
+    Lis { l, .. } => match &**l {
+        Ints(v, _) => Some(Numbers::List(Cow::Borrowed(v))),
+        Bytes(v, _) => Some(Numbers::List(Cow::Owned(v.iter().map(|&x| i64::from(x)).collect()))),
+        _ => None,
+    },
+    _ => None,
+}}
+
+fn as_nums(a: &Val) -> Option<Numbers<'_, c64>> { match a {
+    Int(_) | Num(_) => Some(Numbers::Scalar(a.as_c())),
+    Lis { l, .. } => match &**l {
+        Nums(v, _) => Some(Numbers::List(Cow::Borrowed(v))),
+        Vals(_) => None,
+        l => Some(Numbers::List(Cow::Owned(l.items().map(|x| x.as_c()).collect()))),
+    },
+    _ => None,
+}}
+
+fn zip<T: Copy, U>(a: Numbers<T>, b: Numbers<T>, f: impl Fn(T, T) -> U) -> Option<Vec<U>> {
+    use Numbers::{List, Scalar};
+    Some(match (a, b) {
+        (List(a), List(b)) if a.len() == b.len() => a.iter().zip(b.iter()).map(|(&a, &b)| f(a, b)).collect(),
+        (List(a), Scalar(b)) => a.iter().map(|&a| f(a, b)).collect(),
+        (Scalar(a), List(b)) => b.iter().map(|&b| f(a, b)).collect(),
+        _ => return None,
+    })
+}
+
+/// `a f┼ b` without going through each item, if possible
+pub fn conform(env: &mut Env, f: Func, a: &Val, b: &Val) -> Option<Val> {
+    let (pa, pb) = (packed(a), packed(b));
+    let fits = |x: &Val, p: Option<&List>| p.is_some() || x.is_scalar();
+    if pa.is_none() && pb.is_none() || !fits(a, pa) || !fits(b, pb) { return None }
+    let is = |g: Func| f as usize == g as usize;
+    if let (Some(l), Some(r)) = (as_ints(a), as_ints(b)) {
+        let ints: Option<fn(i64, i64) -> Option<i64>> =
+            if is(func::add) { Some(int_add) }
+            else if is(func::sub) { Some(int_sub) }
+            else if is(func::mul) { Some(int_mul) }
+            else if is(func::max) { Some(|a, b| Some(a.max(b))) }
+            else if is(func::min) { Some(|a, b| Some(a.min(b))) }
+            else if is(func::lt) { Some(|a, b| Some(i64::from(a < b))) }
+            else if is(func::gt) { Some(|a, b| Some(i64::from(a > b))) }
+            else if is(func::matches) { Some(|a, b| Some(i64::from(a == b))) }
+            else { None };
+        // if something overflows, the slow path makes it a bigint
+        if let Some(g) = ints { if let Some(x) = zip(l, r, g).and_then(|x| x.into_iter().collect()) {
+            return Some(List::ints(x).into())
+        }}
+    } else if let (Some(l), Some(r)) = (as_nums(a), as_nums(b)) {
+        let nums: Option<fn(c64, c64) -> c64> =
+            if is(func::add) { Some(|a, b| a + b) }
+            else if is(func::sub) { Some(|a, b| a - b) }
+            else if is(func::mul) { Some(|a, b| a * b) }
+            else { None };
+        if let Some(g) = nums { return zip(l, r, g).map(|x| List::nums(x).into()) }
+    }
+    // any other function, one item at a time
+    let len = match (pa, pb) {
+        (Some(l), Some(r)) if l.len() != r.len() => return None,
+        (Some(l), _) | (_, Some(l)) => l.len(),
+        (None, None) => return None,
+    };
+    let get = |x: &Val, p: Option<&List>, n| p.map_or_else(|| x.c(), |l| l.item(n).unwrap());
+    let items = (0..len).map(|n| f(env, get(a, pa, n), Some(get(b, pb, n))));
+    Some(List::pack(items).map_or_else(|e| e, Val::from))
+}
+
+/// `af┼` or `af┼β` for a scalar β, without going through each item
+pub fn extend(env: &mut Env, f: Func, a: &Val, b: Option<&Val>) -> Option<Val> {
+    let l = packed(a)?;
+    if b.is_some_and(|b| !b.is_scalar()) { return None }
+    if let (Some(Numbers::List(v)), None) = (as_ints(a), b) {
+        if f as usize == func::neg as Func as usize {
+            if let Some(v) = v.iter().map(|x| x.checked_neg()).collect() { return Some(List::ints(v).into()) }
+        }
+    }
+    Some(List::pack(l.items().map(|x| f(env, x, b.cloned()))).map_or_else(|e| e, Val::from))
+}
+
+/// the intrinsic a function boils down to when called with two scalars, like `+` to `inadd`
+pub fn scalar_prim(g: &Val) -> Option<Func> { match g {
+    Val::Func(f) => Some(*f),
+    Val::Av(t, _, g) if *t as usize == adverb::conform as AvT as usize
+        || *t as usize == adverb::valences as AvT as usize => scalar_prim(g),
+    _ => None,
+}}
+
+// i64 arithmetic that gives up on overflow when there are bigints, and saturates otherwise
+#[cfg(feature="bigint")] fn int_add(a: i64, b: i64) -> Option<i64> { a.checked_add(b) }
+#[cfg(feature="bigint")] fn int_sub(a: i64, b: i64) -> Option<i64> { a.checked_sub(b) }
+#[cfg(feature="bigint")] fn int_mul(a: i64, b: i64) -> Option<i64> { a.checked_mul(b) }
+#[cfg(not(feature="bigint"))] fn int_add(a: i64, b: i64) -> Option<i64> { Some(a.saturating_add(b)) }
+#[cfg(not(feature="bigint"))] fn int_sub(a: i64, b: i64) -> Option<i64> { Some(a.saturating_sub(b)) }
+#[cfg(not(feature="bigint"))] fn int_mul(a: i64, b: i64) -> Option<i64> { Some(a.saturating_mul(b)) }
+
+fn int_fold(f: Func) -> Option<fn(i64, i64) -> Option<i64>> {
+    let is = |g: Func| f as usize == g as usize;
+    if is(func::add) { Some(int_add) }
+    else if is(func::mul) { Some(int_mul) }
+    else if is(func::max) { Some(|a, b| Some(a.max(b))) }
+    else if is(func::min) { Some(|a, b| Some(a.min(b))) }
+    else { None }
+}
+
+/// `αg╧β` or `αg╤β` for a packed list of ints
+pub fn fold(g: &Val, a: &Val, b: Option<&Val>, scan: bool) -> Option<Val> {
+    let f = int_fold(scalar_prim(g)?)?;
+    let Numbers::List(v) = as_ints(a)? else { return None };
+    let (start, rest) = match b {
+        None => (*v.first()?, &v[1..]),
+        Some(Int(b)) => (f(*b, *v.first()?)?, &v[1..]),
+        Some(_) => return None,
+    };
+    if !scan { return rest.iter().try_fold(start, |acc, &x| f(acc, x)).map(Int) }
+    let mut acc = start;
+    iter::once(Some(start)).chain(rest.iter().map(|&x| { acc = f(acc, x)?; Some(acc) }))
+        .collect::<Option<_>>().map(|x| List::ints(x).into())
+}
@@ -0,0 +1,115 @@
+use core::cmp::Ordering;
+use num_bigint::{BigInt, Sign};
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, FromPrimitive, Signed, One};
+use crate::prelude::*;
+use super::{Val::{self, Int, Num}, Env, Error, ErrorKind, c64, val::complexcmp};
+
+impl Val {
+    /// an integer, as an `Int` if it fits. a `Big` is never in the range of an i64
+    pub fn big(n: BigInt) -> Val { n.to_i64().map_or_else(|| Val::Big(Rc::new(n)), Int) }
+
+    /// a fraction, as an integer if it is one
+    pub fn rat(n: BigRational) -> Val {
+        if n.is_integer() { Val::big(n.to_integer()) } else { Val::Rat(Rc::new(n)) }
+    }
+
+    pub fn is_integer(&self) -> bool { matches!(self, Int(_) | Val::Big(_)) }
+
+    pub fn is_exact(&self) -> bool { matches!(self, Int(_) | Val::Big(_) | Val::Rat(_)) }
+
+    /// the value of an `Int` or a `Big`
+    pub fn bigint(&self) -> Option<BigInt> { match self {
+        Int(n) => Some(BigInt::from(*n)),
+        Val::Big(n) => Some((**n).c()),
+        _ => None,
+    }}
+}
+
+/// like `try_int`, but for integers of any size
+pub fn int(x: &Val) -> Option<BigInt> { x.bigint().or_else(|| x.try_int().map(BigInt::from)) }
+
+pub fn to_f64(n: &BigInt) -> f64 { n.to_f64().unwrap_or(f64::NAN) }
+
+/// the exact value of a real number. floats are exact too, so `:0.1` isn't one tenth
+pub fn ratio(x: &Val) -> Option<BigRational> { match x {
+    Int(n) => Some(BigRational::from_integer(BigInt::from(*n))),
+    Val::Big(n) => Some(BigRational::from_integer((**n).c())),
+    Val::Rat(n) => Some((**n).c()),
+    Num(n) if n.im == 0. => BigRational::from_float(n.re),
+    _ => None,
+}}
+
+pub fn ratio_to_f64(n: &BigRational) -> f64 { n.to_f64().unwrap_or(f64::NAN) }
+
+/// a whole real number as an integer
+pub fn whole(n: c64) -> Option<BigInt> {
+    if n.im != 0. || n.re.fract() != 0. { return None }
+    BigInt::from_f64(n.re)
+}
+
+/// order of two scalars where at least one is a `Big` or a `Rat`
+pub fn cmp(a: &Val, b: &Val) -> Ordering { match (a, b) {
+    (Num(_), _) => cmp(b, a).reverse(),
+    (a, Num(n)) => match ratio(b) {
+        Some(n) => ratio(a).cmp(&Some(n)),
+        // n is infinite, nan or complex
+        None => complexcmp(a.as_c(), *n).then(if n.re > 0. { Ordering::Less } else { Ordering::Greater }),
+    },
+    (a, b) => ratio(a).cmp(&ratio(b)),
+}}
+
+/// fail if an integer of `bits` bits would be too big
+pub fn check_bits(env: &Env, bits: f64) -> Result<(), Val> {
+    if bits > env.max_alloc as f64 { return Err(Error::new(ErrorKind::Limit, "number too big").into()) }
+    Ok(())
+}
+
+pub fn pow(env: &Env, a: BigInt, b: u32) -> Val {
+    ok!(check_bits(env, a.bits() as f64 * f64::from(b)));
+    Val::big(a.pow(b))
+}
+
+// like the i64 methods. b can't be zero
+pub fn rem_euclid(a: &BigInt, b: &BigInt) -> BigInt {
+    let r = a % b;
+    if r.is_negative() { r + b.abs() } else { r }
+}
+pub fn div_euclid(a: &BigInt, b: &BigInt) -> BigInt {
+    let q = a / b;
+    if !(a % b).is_negative() { q } else if b.is_positive() { q - 1 } else { q + 1 }
+}
+
+pub fn pow_ratio(env: &Env, a: BigRational, b: i32) -> Val {
+    ok!(check_bits(env, (a.numer().bits() + a.denom().bits()) as f64 * f64::from(b.unsigned_abs())));
+    Val::rat(a.pow(b))
+}
+
+/// euclidean quotient and remainder, like `div_euclid` and `rem_euclid`. b can't be zero
+pub fn divrem_ratio(a: &BigRational, b: &BigRational) -> (BigInt, BigRational) {
+    let q = a / b;
+    let q = if b.is_positive() { q.floor() } else { q.ceil() };
+    (q.to_integer(), a - b * q)
+}
+
+pub fn fact(env: &mut Env, n: i64) -> Val {
+    ok!(check_bits(env, libm::lgamma(n as f64 + 1.) / core::f64::consts::LN_2));
+    let mut acc = BigInt::one();
+    for i in 2..=n { ok!(env.tick()); acc *= i; }
+    Val::big(acc)
+}
+
+/// `n choose k` for 0 ≤ k ≤ n
+pub fn binom(env: &mut Env, n: &BigInt, k: &BigInt) -> Val {
+    let k = k.min(&(n - k)).c();
+    let Some(steps) = k.to_u64() else { return Error::new(ErrorKind::Limit, "number too big").into() };
+    ok!(check_bits(env, steps as f64 * to_f64(n).log2()));
+    let mut acc = BigInt::one();
+    for i in 0..steps {
+        ok!(env.tick());
+        acc = acc * (n - i) / (i + 1);
+    }
+    Val::big(acc)
+}
+
+pub fn sign(n: &BigInt) -> Val { Int(match n.sign() { Sign::Minus => -1, Sign::NoSign => 0, Sign::Plus => 1 }) }
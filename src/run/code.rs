@@ -0,0 +1,331 @@
+use crate::parse::{Expr, Stmt, Pat};
+use crate::token::Span;
+use crate::prelude::*;
+use super::{Env, Frame, Val::{self, Int, Num}, List, NAN, Fallible, at, c64};
+
+/// a compiled block of statements, run on a small value stack by `Env::exec`
+#[derive(Debug)]
+pub struct Code {
+    pub ops: Vec<Op>,
+    /// every variable name the code mentions. ops refer to variables by their index here
+    pub names: Rc<[Bstr]>,
+    /// whether the code runs in its own frame, where each name has a slot with the same index.
+    /// the top level and `eval` don't, so they look everything up by name
+    pub slotted: bool,
+    /// slots that are captured when the function is created
+    pub cap: Vec<usize>,
+    /// what the code was compiled from, for displaying functions
+    pub src: Vec<Stmt>,
+}
+
+/// the slots of the arguments in a function's frame
+pub const ALPHA: usize = 0;
+pub const BETA: usize = 1;
+pub const ARITY: usize = 2;
+pub const SELF: usize = 3;
+
+#[derive(Debug)]
+pub enum Op {
+    /// push a variable
+    Var(usize),
+    Int(i64), Flt(c64),
+    SetVar(usize), MutVar(usize),
+    SetPat(Rc<Pat>),
+    /// make a strand out of the top n values
+    Snd(usize),
+    /// α, f → αf
+    Call1(Span),
+    /// α, f, β → αfβ
+    Call2(Span),
+    /// calls whose result is returned right away. if f is the function being run, its frame is
+    /// reused instead of making a new one
+    Tail1(Span), Tail2(Span),
+    /// goes between the f and β of a dyadic call. if f is an assignment, does it right away and
+    /// leaves a function that returns β, so `α→x β` evaluates to β
+    PreCall2,
+    /// g → the adverb in the variable applied to g
+    Aav1(usize),
+    /// f, g → the adverb in the variable applied to g and f
+    Aav2(usize),
+    Bind, Trn2, Trn3, Fork,
+    /// alternating keys and values → dict
+    Dict,
+    Dfn { code: Rc<Code>, span: Span },
+    Pop,
+    /// pop a value into a local variable
+    Loc(usize), Mut(usize, Span),
+    DelLoc(usize), DelMut(usize),
+    /// pop a value and assign its parts
+    LocPat(Rc<Pat>),
+    /// pop a condition and jump if it's false
+    JumpUnless(usize, Span),
+    Jump(usize),
+}
+
+/// compile statements to run in an existing frame
+pub fn compile(src: Vec<Stmt>) -> Code {
+    let mut c = Compiler { ops: Vec::new(), names: Vec::new() };
+    c.block(&src);
+    Code { ops: c.ops, names: c.names.into(), slotted: false, cap: Vec::new(), src }
+}
+
+/// compile the body of a function
+fn compile_dfn(src: Vec<Stmt>, cap: &HashSet<Bstr>) -> Code {
+    let names = vec![bstr![b!('α')], bstr![b!('β')], bstr![b!('Σ')], bstr![b!('ƒ')]];
+    let mut c = Compiler { ops: Vec::new(), names };
+    let cap = cap.iter().map(|x| c.name(x)).collect();
+    c.block(&src);
+    for i in 0..c.ops.len() {
+        let returns = returns(&c.ops, i + 1);
+        match c.ops[i] {
+            Op::Call1(span) if returns => c.ops[i] = Op::Tail1(span),
+            Op::Call2(span) if returns => c.ops[i] = Op::Tail2(span),
+            _ => (),
+        }
+    }
+    Code { ops: c.ops, names: c.names.into(), slotted: true, cap, src }
+}
+
+/// whether running from `pc` only jumps to the end
+fn returns(ops: &[Op], mut pc: usize) -> bool {
+    loop { match ops.get(pc) {
+        None => return true,
+        Some(Op::Jump(to)) => pc = *to,
+        Some(_) => return false,
+    }}
+}
+
+/// the frame a function runs in
+pub fn dfn_frame(f: &Val, loc: &Frame, a: Val, b: Option<Val>) -> Frame {
+    let mut frame = loc.c();
+    frame.slots[ARITY] = Some(Int(1 + i64::from(b.is_some())));
+    frame.slots[ALPHA] = Some(a);
+    frame.slots[BETA] = Some(b.unwrap_or(NAN));
+    frame.slots[SELF] = Some(f.c());
+    frame
+}
+
+struct Compiler { ops: Vec<Op>, names: Vec<Bstr> }
+
+impl Compiler {
+    fn name(&mut self, name: &Bstr) -> usize {
+        self.names.iter().position(|x| x == name).unwrap_or_else(|| {
+            self.names.push(name.c());
+            self.names.len() - 1
+        })
+    }
+
+    // the names get slots like the ones of other assignments
+    fn pat(&mut self, p: &Pat) -> Rc<Pat> {
+        for i in p.names() { self.name(i); }
+        Rc::new(p.c())
+    }
+
+    fn block(&mut self, s: &[Stmt]) {
+        let mut returns = Vec::new();
+        for i in s { self.stmt(i, &mut returns); }
+        self.ops.push(Op::Flt(c64::new(f64::NAN, f64::NAN)));
+        let end = self.ops.len();
+        for i in returns { self.ops[i] = Op::Jump(end); }
+    }
+
+    fn stmt(&mut self, s: &Stmt, returns: &mut Vec<usize>) {
+        match s {
+            Stmt::Discard(e) => { self.expr(e); self.ops.push(Op::Pop); },
+            Stmt::Return(e) => { self.expr(e); returns.push(self.ops.len()); self.ops.push(Op::Jump(0)); },
+            Stmt::Loc(e, v) => { self.expr(e); let v = self.name(v); self.ops.push(Op::Loc(v)); },
+            Stmt::Mut(e, v, span) => { self.expr(e); let v = self.name(v); self.ops.push(Op::Mut(v, *span)); },
+            Stmt::DelLoc(v) => { let v = self.name(v); self.ops.push(Op::DelLoc(v)); },
+            Stmt::DelMut(v) => { let v = self.name(v); self.ops.push(Op::DelMut(v)); },
+            Stmt::LocPat(e, p) => { self.expr(e); let p = self.pat(p); self.ops.push(Op::LocPat(p)); },
+            Stmt::Cond(cond, then, span) => {
+                self.expr(cond);
+                let jump = self.ops.len();
+                self.ops.push(Op::JumpUnless(0, *span));
+                self.stmt(then, returns);
+                self.ops[jump] = Op::JumpUnless(self.ops.len(), *span);
+            },
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        let op = match e {
+            Expr::Var(s) => Op::Var(self.name(s)),
+            Expr::Int(n) => Op::Int(*n),
+            Expr::Flt(n) => Op::Flt(*n),
+            Expr::SetVar(v) => Op::SetVar(self.name(v)),
+            Expr::MutVar(v) => Op::MutVar(self.name(v)),
+            Expr::SetPat(p) => Op::SetPat(self.pat(p)),
+            Expr::Snd(l) => { for x in l { self.expr(x); } Op::Snd(l.len()) },
+            Expr::Afn1(a, f, span) => { self.expr(a); self.expr(f); Op::Call1(*span) },
+            // assignments don't need to make a function
+            Expr::Afn2(a, f, b, _) if matches!(**f, Expr::SetVar(_)) => {
+                let Expr::SetVar(v) = &**f else { unreachable!() };
+                self.expr(a);
+                let v = self.name(v);
+                self.ops.push(Op::Loc(v));
+                return self.expr(b)
+            },
+            Expr::Afn2(a, f, b, span) => {
+                self.expr(a); self.expr(f); self.ops.push(Op::PreCall2);
+                self.expr(b); Op::Call2(*span)
+            },
+            Expr::Aav1(v, g) => { self.expr(g); Op::Aav1(self.name(v)) },
+            Expr::Aav2(f, v, g) => { self.expr(f); self.expr(g); Op::Aav2(self.name(v)) },
+            Expr::Bind(f, b) => { self.expr(f); self.expr(b); Op::Bind },
+            Expr::Trn2(a, f) => { self.expr(a); self.expr(f); Op::Trn2 },
+            Expr::Trn3(a, f, b) => { self.expr(a); self.expr(f); self.expr(b); Op::Trn3 },
+            Expr::Fork(a, f, b) => { self.expr(a); self.expr(f); self.expr(b); Op::Fork },
+            Expr::Dfn { s, cap, span } => Op::Dfn { code: Rc::new(compile_dfn(s.c(), cap)), span: *span },
+            Expr::Block(s) => return self.block(s),
+            Expr::Dict(e) => { self.expr(e); Op::Dict },
+        };
+        self.ops.push(op);
+    }
+}
+
+impl<'io> Env<'io> {
+    fn var(&self, code: &Code, i: usize) -> Val {
+        if code.slotted { if let Some(x) = &self.locals().slots[i] { return x.c() } }
+        self.get_var(&code.names[i]).unwrap_or_default()
+    }
+
+    /// run compiled code in the current frame
+    pub fn exec(&mut self, code: &Code) -> Val {
+        let mut stack: Vec<Val> = Vec::new();
+        let mut pc = 0;
+        macro_rules! pop { () => { stack.pop().unwrap() } }
+        macro_rules! push { ($v:expr) => { stack.push(ok!($v)) } }
+        while let Some(op) = code.ops.get(pc) {
+            ok!(self.tick());
+            pc += 1;
+            match op {
+                Op::Var(v) => push!(self.var(code, *v)),
+                Op::Int(n) => stack.push(Int(*n)),
+                Op::Flt(n) => stack.push(Num(*n)),
+                Op::SetVar(v) => stack.push(Val::FSet(code.names[*v].c())),
+                Op::MutVar(v) => stack.push(Val::FCng(code.names[*v].c())),
+                Op::SetPat(p) => stack.push(Val::FPat(p.c())),
+                Op::Snd(n) => {
+                    let v = stack.split_off(stack.len() - n);
+                    stack.push(if v.iter().all(|x| matches!(x, Int(_))) {
+                        List::ints(v.into_iter().filter_map(|x| x.try_int()).collect()).into()
+                    } else { Val::lis(v) });
+                },
+                Op::Call1(span) => {
+                    let f = pop!(); let a = pop!();
+                    push!(at(f.monad(self, a), span));
+                },
+                Op::Tail1(span) | Op::Tail2(span) => {
+                    let b = if let Op::Tail2(_) = op { Some(pop!()) } else { None };
+                    let f = pop!(); let a = pop!();
+                    match &f {
+                        Val::Dfn { code: c, loc, .. } if core::ptr::eq(&**c, code) => {
+                            *self.locals_mut() = dfn_frame(&f, loc, a, b);
+                            stack.clear();
+                            pc = 0;
+                        },
+                        _ => push!(at(f.call(self, a, b), span)),
+                    }
+                },
+                Op::PreCall2 => if let Some(Val::FSet(_) | Val::FPat(_)) = stack.last() {
+                    let f = pop!(); let a = pop!();
+                    ok!(f.monad(self, a));
+                    stack.push(NAN); stack.push(Val::Func(super::func::right));
+                },
+                Op::Call2(span) => {
+                    let b = pop!(); let f = pop!(); let a = pop!();
+                    push!(at(f.dyad(self, a, b), span));
+                },
+                Op::Aav1(v) => {
+                    let g = pop!();
+                    push!(self.var(code, *v).monad(self, g));
+                },
+                Op::Aav2(v) => {
+                    let g = pop!(); let f = pop!();
+                    push!(self.var(code, *v).dyad(self, g, f));
+                },
+                Op::Bind => {
+                    let b = pop!(); let f = pop!();
+                    stack.push(Val::bind(f.rc(), b.rc()));
+                },
+                Op::Trn2 => {
+                    let f = pop!(); let a = pop!();
+                    stack.push(Val::atop(a.rc(), f.rc()));
+                },
+                Op::Trn3 => {
+                    let b = pop!(); let f = pop!(); let a = pop!();
+                    stack.push(Val::atop(a.rc(), Val::bind(f.rc(), b.rc()).rc()));
+                },
+                Op::Fork => {
+                    let b = pop!(); let f = pop!(); let a = pop!();
+                    stack.push(Val::Fork(a.rc(), f.rc(), b.rc()));
+                },
+                Op::Dict => {
+                    let a = pop!();
+                    push!(super::dict::dict(self, a, None));
+                },
+                Op::Dfn { code: inner, span } => {
+                    let mut frame = Frame::new(inner.names.c());
+                    for &i in &inner.cap { frame.slots[i] = self.get_var_cap(&inner.names[i]); }
+                    stack.push(Val::Dfn { code: inner.c(), loc: Rc::new(frame), span: *span });
+                },
+                Op::Pop => { stack.pop(); },
+                Op::Loc(v) => {
+                    let a = pop!();
+                    if code.slotted { self.locals_mut().slots[*v] = Some(a); }
+                    else { self.set_local(code.names[*v].c(), a); }
+                },
+                Op::Mut(v, span) => {
+                    let a = pop!();
+                    if let Some(e @ Val::Err(_)) = self.mutate_var(&code.names[*v], a, None) { return at(e, span) }
+                },
+                Op::LocPat(p) => {
+                    let a = pop!();
+                    ok!(destructure(self, p, a));
+                },
+                Op::DelLoc(v) => { self.locals_mut().remove(&code.names[*v]); },
+                Op::DelMut(v) => self.delete_var(&code.names[*v]),
+                Op::JumpUnless(to, span) => {
+                    let val = pop!();
+                    let cond = val.is_scalar() && val.as_bool() || {
+                        let a = self.locals().get(&[b!('α')]).cloned().unwrap_or(NAN);
+                        let b = self.locals().get(&[b!('Σ')])
+                            .map_or(false, |x| x.try_int() != Some(1))
+                            .then(|| self.locals().get(&[b!('β')]).cloned().unwrap_or(NAN) );
+                        ok!(at(val.call(self, a, b), span)).as_bool()
+                    };
+                    if !cond { pc = *to; }
+                },
+                Op::Jump(to) => pc = *to,
+            }
+        }
+        stack.pop().unwrap_or_default()
+    }
+}
+
+/// assign the items of `a` to the names in `pat`. items after a rest pattern are taken from the
+/// end, and missing ones are the fill. fails if getting an item does
+pub fn destructure(env: &mut Env, pat: &Pat, a: Val) -> Result<(), Val> {
+    let l = match pat {
+        Pat::Name(n) | Pat::Rest(Some(n)) => { env.set_local(n.c(), a); return Ok(()) },
+        Pat::Skip | Pat::Rest(None) => return Ok(()),
+        Pat::List(l) => l,
+    };
+    let rest = l.iter().position(|x| matches!(x, Pat::Rest(_)));
+    let (len, after) = (a.len(), rest.map_or(0, |r| l.len() - r - 1));
+    for (i, p) in l.iter().enumerate() { match rest {
+        Some(r) if i == r => {
+            let Pat::Rest(Some(n)) = p else { continue };
+            let fill = if a.is_list() { a.fill() } else { NAN };
+            let items = a.iterf().skip(r).take(len.saturating_sub(r + after)).cloned().collect();
+            env.set_local(n.c(), Val::lis_fill(items, fill));
+        },
+        Some(r) if i > r => {
+            let x = a.index(env, len.saturating_sub(after).max(r) + i - r - 1).into_result()?;
+            destructure(env, p, x)?;
+        },
+        _ => { let x = a.index(env, i).into_result()?; destructure(env, p, x)?; },
+    }}
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use core::hash::{Hash, Hasher};
+use crate::prelude::*;
+use super::{Val::{self, Int}, NAN, Error, ErrorKind};
+
+/// a map from values to values. entries stay in the order their keys were first inserted in
+#[derive(Clone, Default)]
+pub struct Dict {
+    keys: Vec<Val>,
+    vals: Vec<Val>,
+    index: HashMap<Val, usize>,
+}
+
+impl Dict {
+    pub fn len(&self) -> usize { self.keys.len() }
+    pub fn is_empty(&self) -> bool { self.keys.is_empty() }
+
+    pub fn keys(&self) -> &[Val] { &self.keys }
+    pub fn values(&self) -> &[Val] { &self.vals }
+    pub fn into_values(self) -> Vec<Val> { self.vals }
+
+    pub fn iter(&self) -> impl Iterator<Item=(&Val, &Val)> { self.keys.iter().zip(&self.vals) }
+
+    pub fn get(&self, key: &Val) -> Option<&Val> { self.index.get(key).map(|&n| &self.vals[n]) }
+
+    /// replace the value of an existing key where it is, or add it at the end
+    pub fn insert(&mut self, key: Val, val: Val) {
+        if let Some(&n) = self.index.get(&key) { self.vals[n] = val; return }
+        self.index.insert(key.c(), self.keys.len());
+        self.keys.push(key); self.vals.push(val);
+    }
+
+    pub fn remove(&mut self, key: &Val) -> Option<Val> {
+        let n = self.index.remove(key)?;
+        for i in self.index.values_mut() { if *i > n { *i -= 1; } }
+        self.keys.remove(n);
+        Some(self.vals.remove(n))
+    }
+
+    /// the same keys with other values, in order
+    pub fn with_values(&self, vals: Vec<Val>) -> Dict {
+        Dict { keys: self.keys.c(), vals, index: self.index.c() }
+    }
+
+    /// get the dict out of an rc, cloning only if needed
+    pub fn unwrap(d: Rc<Dict>) -> Dict { Rc::try_unwrap(d).unwrap_or_else(|d| (*d).c()) }
+}
+
+impl FromIterator<(Val, Val)> for Dict {
+    fn from_iter<T: IntoIterator<Item = (Val, Val)>>(iter: T) -> Self {
+        let mut dict = Dict::default();
+        for (k, v) in iter { dict.insert(k, v); }
+        dict
+    }
+}
+
+impl From<Dict> for Val {
+    fn from(d: Dict) -> Val { Val::Dict(Rc::new(d)) }
+}
+
+// dicts with the same entries in a different order are different
+impl PartialEq for Dict {
+    fn eq(&self, other: &Dict) -> bool { self.keys == other.keys && self.vals == other.vals }
+}
+
+impl Hash for Dict {
+    fn hash<H: Hasher>(&self, state: &mut H) { (&self.keys, &self.vals).hash(state); }
+}
+
+func!(a :dict b? => match (a, b) {
+    (a @ Val::Dict(_), None) => a,
+    (a, _) if a.is_infinite() => Error::new(ErrorKind::Type, "can't make a dict of a function").prim("_♫").into(),
+    (a, Some(b)) => a.into_iterf().zip(b.into_iterf()).collect::<Dict>().into(),
+    // keys and values alternate
+    (a, None) => {
+        let mut iter = a.into_iterf();
+        let mut dict = Dict::default();
+        while let Some(k) = iter.next() { dict.insert(k, iter.next().unwrap_or(NAN)); }
+        dict.into()
+    }
+});
+
+func!(a :keys => match a {
+    Val::Dict(d) => Val::lis(d.keys.c()),
+    a if a.is_infinite() => Error::new(ErrorKind::Type, "a function has no keys").prim("_↑").into(),
+    a => (0..a.len() as i64).map(Int).collect(),
+});
+
+func!(a :values => match a {
+    Val::Dict(d) => Val::lis(Dict::unwrap(d).vals),
+    a => a,
+});
+
+func!(a :items => match a {
+    Val::Dict(d) => d.iter().map(|(k, v)| Val::lis(vec![k.c(), v.c()])).collect(),
+    _ => Error::new(ErrorKind::Type, "α isn't a dict").prim("_,").into(),
+});
+
+func!(a :insert b => {
+    let Val::Dict(d) = a else { return Error::new(ErrorKind::Type, "α isn't a dict").prim("_↑").into() };
+    let mut d = Dict::unwrap(d);
+    let mut iter = b.into_iterf();
+    let Some(k) = iter.next() else { return d.into() };
+    d.insert(k, iter.next().unwrap_or(NAN));
+    d.into()
+});
+
+func!(a :delete b => {
+    let Val::Dict(d) = a else { return Error::new(ErrorKind::Type, "α isn't a dict").prim("_↓").into() };
+    let mut d = Dict::unwrap(d);
+    d.remove(&b);
+    d.into()
+});
+
+func!(a :merge b => {
+    let (Val::Dict(l), Val::Dict(r)) = (a, b) else {
+        return Error::new(ErrorKind::Type, "α and β have to be dicts").prim("_,").into()
+    };
+    let mut l = Dict::unwrap(l);
+    for (k, v) in r.iter() { l.insert(k.c(), v.c()); }
+    l.into()
+});
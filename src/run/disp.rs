@@ -89,6 +89,8 @@ pub fn format(&self, f: &mut impl Write, slice: &[Val]) -> FResult {
             if n.im != 0. { write!(f, "{:+}i", n.im)? };
         Ok(()) },
         Int(n) => write!(f, "{n}"),
+        #[cfg(feature="bigint")] Val::Big(n) => write!(f, "{n}"),
+        #[cfg(feature="bigint")] Val::Rat(n) => write!(f, "{n}"),
         Lis { l, fill } => {
             let mut iter = l.iter();
             write!(f, "(")?;
@@ -97,8 +99,17 @@ pub fn format(&self, f: &mut impl Write, slice: &[Val]) -> FResult {
             write!(f, ")")?;
             if !fill.is_nan() { write!(f, "▐{fill}")?; }
         Ok(()) },
+        Val::Dict(d) => {
+            write!(f, "⎕(")?;
+            for (n, (k, v)) in d.iter().enumerate() {
+                if n != 0 { write!(f, " ")?; }
+                k.format(f, slice)?; write!(f, " ")?; v.format(f, slice)?;
+            }
+            write!(f, ")")
+        },
         Val::FSet(x) => write!(f, "→{}", crate::codepage::tochars(x)),
-        Val::Err(x) => write!(f, "ERROR ERROR {x}"),
+        Val::FPat(p) => write!(f, "→{p}"),
+        Val::Err(e) => write!(f, "{e}"),
         _ => write!(f, "<function>"),
     }
 }}
@@ -125,7 +136,9 @@ impl<'a> alloc::fmt::Write for Indent<'a> {
 
 impl Val {
     pub fn display_string(&self) -> String {
-        if let Some(n) = self.try_c() {
+        if matches!(self, Int(_)) || self.is_bignum() {
+            format!("{self}")
+        } else if let Some(n) = self.try_c() {
             if n.is_nan() { String::new() }
             else if n.im != 0. { format!("{}{:+}i", n.re, n.im) }
             else { format!("{}", n.re) }
@@ -136,4 +149,26 @@ impl Val {
             )).collect()
         } else { format!("{self}") }
     }
-}
\ No newline at end of file
+}
+
+impl Val {
+    /// short description of a value, showing the structure of functions. used in stack traces
+    pub fn describe(&self) -> String {
+        use super::func::intrinsic_name;
+        let name = |ptr: usize| intrinsic_name(ptr).map_or_else(|| String::from("<function>"), |x| format!("in{x}"));
+        match self {
+            Val::Dfn { code, .. } => {
+                let mut buf = String::from("{ ");
+                for i in code.src.iter() { _ = write!(buf, "{i} "); }
+            buf + "}" },
+            Val::Fork(a, f, b) => format!("└({} {} {})", a.describe(), f.describe(), b.describe()),
+            Val::Av(t, Some(f), g) => format!("[{} {} {}]", f.describe(), name(*t as usize), g.describe()),
+            Val::Av(t, None, g) => format!("[{} {}]", name(*t as usize), g.describe()),
+            Val::Memo(m) => format!("[inmemo {}]", m.f.describe()),
+            Val::AvBuilder(t) => name(*t as usize),
+            Val::Func(f) => name(*f as usize),
+            Val::FCng(x) => format!("↔{}", crate::codepage::tochars(x)),
+            _ => format!("{self}"),
+        }
+    }
+}
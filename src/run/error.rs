@@ -0,0 +1,117 @@
+use core::fmt::{Debug, Display, Formatter, Result as FResult};
+use crate::prelude::*;
+use super::Val;
+use crate::token::Span;
+
+/// what went wrong
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// `⌂` was called. not really an error, so it isn't reported
+    Exit,
+    /// the code couldn't be parsed
+    Syntax,
+    /// raised by the program itself with `α⌂β`
+    User,
+    /// went over `Env::max_depth` or `Env::max_alloc`
+    Limit,
+    /// `Env::fuel` ran out
+    Fuel,
+    /// `Env::cancel` was set
+    Cancelled,
+    /// a file given to `_ò` couldn't be found or loaded
+    Import,
+    /// a function was asked to undo itself but can't
+    Inverse,
+    /// an argument has the wrong type, like a number where a dict is expected.
+    /// values that are just missing, like `123@5` or `0/0`, give `■` instead
+    Type,
+    /// an argument has the right type but is out of the function's domain
+    Domain,
+    /// an index isn't an integer
+    Index,
+}
+
+impl ErrorKind {
+    pub fn name(self) -> &'static str { match self {
+        ErrorKind::Exit => "exit",
+        ErrorKind::Syntax => "syntax",
+        ErrorKind::User => "user",
+        ErrorKind::Limit => "limit",
+        ErrorKind::Fuel => "out of fuel",
+        ErrorKind::Cancelled => "cancelled",
+        ErrorKind::Import => "import",
+        ErrorKind::Inverse => "no inverse",
+        ErrorKind::Type => "type",
+        ErrorKind::Domain => "domain",
+        ErrorKind::Index => "index",
+    }}
+}
+
+/// a vemf runtime error. carried by `Val::Err` and returned by `Env::run_bytes`
+#[derive(Clone)]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// process exit code
+    pub code: i32,
+    pub message: String,
+    /// name of the primitive that raised the error, if any
+    pub prim: Option<&'static str>,
+    pub payload: Option<Val>,
+    /// where in the source it happened, if known
+    pub span: Option<Span>,
+    /// functions the error went through, innermost first. only filled if `Env::trace` is set
+    pub trace: Vec<TraceFrame>,
+}
+
+/// a function call in a stack trace
+#[derive(Clone, Debug)]
+pub struct TraceFrame {
+    pub func: String,
+    /// shapes of the arguments, α first
+    pub shapes: Vec<Vec<usize>>,
+    /// where the function was defined, for dfns
+    pub span: Option<Span>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Error {
+        Error { kind, code: 1, message: message.into(), prim: None, payload: None, span: None, trace: Vec::new() }
+    }
+    pub fn code(self, code: i32) -> Error { Error { code, ..self } }
+    pub fn prim(self, prim: &'static str) -> Error { Error { prim: Some(prim), ..self } }
+    pub fn payload(self, payload: Val) -> Error { Error { payload: Some(payload), ..self } }
+    pub fn at(self, span: Span) -> Error { Error { span: Some(span), ..self } }
+}
+
+impl From<Error> for Val {
+    fn from(e: Error) -> Val { Val::Err(Rc::new(e)) }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "{} error", self.kind.name())?;
+        if let Some(p) = self.prim { write!(f, " in {p}")?; }
+        if let Some(s) = self.span { write!(f, " at {}:{}", s.line, s.col)?; }
+        if !self.message.is_empty() { write!(f, ": {}", self.message)?; }
+        if let (true, Some(p)) = (self.message.is_empty(), &self.payload) { write!(f, ": {p}")?; }
+        for t in &self.trace { write!(f, "\n  {t}")?; }
+        Ok(())
+    }
+}
+
+impl Display for TraceFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult {
+        write!(f, "in {}", self.func)?;
+        for (name, shape) in ["α", "β"].iter().zip(&self.shapes) {
+            write!(f, " {name}:(")?;
+            for (n, i) in shape.iter().enumerate() { write!(f, "{}{i}", if n == 0 {""} else {" "})?; }
+            write!(f, ")")?;
+        }
+        if let Some(s) = self.span { write!(f, " defined at {}:{}", s.line, s.col)?; }
+        Ok(())
+    }
+}
+
+impl Debug for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FResult { write!(f, "{self} (code {})", self.code) }
+}
@@ -1,5 +1,6 @@
 use crate::prelude::*;
-use super::{Val::{self, Num, Int}, NAN, adverb, c64, val::complexcmp, list};
+use super::{Val::{self, Num, Int}, NAN, Error, ErrorKind, List, adverb, c64, val::complexcmp, list};
+#[cfg(feature="bigint")] use {super::big, num_bigint::BigInt, num_traits::{Zero, Signed}};
 
 #[macro_export]
 macro_rules! func {
@@ -25,35 +26,39 @@ macro_rules! intfunc {
     };
 }
 
-pub fn load_intrinsics(env: &mut super::Env) {
-    use list::*; use super::val::encode;
-    macro_rules! load_func {($($name:ident,)*) => { $( {
-        let mut name = Bstr::from(&b"in"[..]);
-        name.extend(stringify!($name).bytes());
-        env.set_local(name, Val::Func($name))
-    } );* }}
-    load_func!(
-        add, sub, mul, div, dive, rem, pow, log, lt, gt, and, or, max, min, atan2, approx, band, bor, bxor, fact, gcd, lcm, binom, abs, neg, ln, exp, sin, asin, cos, acos, tan, atan, sqrt, round, ceil, floor, isnan, sign, bnot, brepr, complex, cis, real, imag, conj, arg,
-        left, right, get, set, call, islist, eval,
+macro_rules! table {($t:ty; $($name:path,)*) => { &[$((stringify!($name), $name as $t),)*] }}
+
+pub const FUNCS: &[(&str, super::Func)] = { use list::*; use super::dict::*; use super::val::encode; use super::memo::{memo, memolimit, memoclear}; table!(super::Func;
+        add, sub, mul, div, exact, float, dive, rem, pow, log, lt, gt, and, or, max, min, atan2, approx, band, bor, bxor, fact, gcd, lcm, binom, abs, neg, ln, exp, sin, asin, cos, acos, tan, atan, sqrt, round, ceil, floor, isnan, sign, bnot, brepr, complex, cis, real, imag, conj, arg,
+        left, right, get, set, call, islist, eval, import,
         shape, len, index, iota, pair, enlist, ravel, concat, reverse, getfill, setfill, matches,
         print, println, output, input, fromutf8, toutf8, fromcp, tocp, exit, format, numfmt, parse,
         takeleft, takeright, dropleft, dropright, replist, pick, sample, replicate, find, uio,
+        indexof, member, nubsieve, intersect, difference, union,
         reverse, gradeup, gradedown, sortup, sortdown, binsup, binsdown, encode, group, occcount,
-        domainto,
-    );
-    macro_rules! load_av {($($name:ident,)*) => { $( {
-        let mut name = Bstr::from(&b"in"[..]);
-        name.extend(stringify!($name).to_ascii_lowercase().bytes());
-        env.set_local(name, Val::AvBuilder(adverb::$name))
-    } );* }}
-    load_av!(
+        domainto, dict, keys, values, items, insert, delete, merge, memo, memolimit, memoclear,
+    ) };
+
+pub const ADVERBS: &[(&str, adverb::AvT)] = { use adverb::*; table!(AvT;
         swap, constant, toleft, toright, bind, atop,
         each, eachleft, eachtrim, conform, extend,
         scan, scanpairs, reduce, stencil, valences,
         overleft, overright, over, forkleft, forkright,
         until, untilscan, power, powerscan, untilcmp, untilscancmp,
-        drill, amend, cycle,
-    );
+        drill, rank, amend, under, undo, cycle, catch,
+        sortby, gradeby, sortwith, gradewith,
+    ) };
+
+pub fn load_intrinsics(env: &mut super::Env) {
+    fn name(n: &str) -> Bstr { let mut name = Bstr::from(&b"in"[..]); name.extend(n.bytes()); name }
+    for (n, f) in FUNCS { env.set_local(name(n), Val::Func(*f)); }
+    for (n, t) in ADVERBS { env.set_local(name(n), Val::AvBuilder(*t)); }
+}
+
+/// name of an intrinsic function or adverb, given its address
+pub fn intrinsic_name(ptr: usize) -> Option<&'static str> {
+    FUNCS.iter().find(|x| x.1 as usize == ptr).map(|x| x.0)
+    .or_else(|| ADVERBS.iter().find(|x| x.1 as usize == ptr).map(|x| x.0))
 }
 
 func!(@env, a :set b => {
@@ -64,30 +69,80 @@ func!(@env, a :get => {
     let name = a.iterf().filter_map(|x| x.try_int().map(|x| x as u8)).collect::<Bstr>();
     env.get_var(&name).unwrap_or(NAN)
 });
+func!(@env, a :import b? => {
+    let bytes = |x: &Val| x.iterf().filter_map(|x| x.try_int().map(|x| x as u8)).collect::<Bstr>();
+    let prefix = b.as_ref().map_or_else(Bstr::new, bytes);
+    env.import(&crate::codepage::tochars(&bytes(&a)), &prefix)
+});
 func!(a :left => a);
 func!(a :right b? => b.unwrap_or(a));
 func!(a :islist => Val::bool(!a.is_scalar()));
 
+// the result of an i64 operation that overflowed: a bigint, or the saturated result without them
+#[cfg(feature="bigint")]
+macro_rules! promote { ($a:tt $op:tt $b:tt, $saturated:expr) => {
+    Val::big(BigInt::from($a) $op BigInt::from($b))
+} }
+#[cfg(not(feature="bigint"))]
+macro_rules! promote { ($a:tt $op:tt $b:tt, $saturated:expr) => { Int($saturated) } }
+
 func!(a :add b => match (a, b) {
-    (Int(a), Int(b)) => Int(a.saturating_add(b)),
+    (Int(a), Int(b)) => a.checked_add(b).map_or_else(|| promote!(a + b, a.saturating_add(b)), Int),
+    #[cfg(feature="bigint")]
+    (a, b) if a.is_integer() && b.is_integer() => Val::big(a.bigint().unwrap() + b.bigint().unwrap()),
+    #[cfg(feature="bigint")]
+    (a, b) if a.is_exact() && b.is_exact() => Val::rat(big::ratio(&a).unwrap() + big::ratio(&b).unwrap()),
     (a, b) => Num(a.as_c() + b.as_c()),
 });
 
 func!(a :sub b => match (a, b) {
-    (Int(a), Int(b)) => Int(a.saturating_sub(b)),
+    (Int(a), Int(b)) => a.checked_sub(b).map_or_else(|| promote!(a - b, a.saturating_sub(b)), Int),
+    #[cfg(feature="bigint")]
+    (a, b) if a.is_integer() && b.is_integer() => Val::big(a.bigint().unwrap() - b.bigint().unwrap()),
+    #[cfg(feature="bigint")]
+    (a, b) if a.is_exact() && b.is_exact() => Val::rat(big::ratio(&a).unwrap() - big::ratio(&b).unwrap()),
     (a, b) => Num(a.as_c() - b.as_c()),
 });
 func!(a :mul b => match (a, b) {
-    (Int(a), Int(b)) => Int(a.saturating_mul(b)),
+    (Int(a), Int(b)) => a.checked_mul(b).map_or_else(|| promote!(a * b, a.saturating_mul(b)), Int),
+    #[cfg(feature="bigint")]
+    (a, b) if a.is_integer() && b.is_integer() => Val::big(a.bigint().unwrap() * b.bigint().unwrap()),
+    #[cfg(feature="bigint")]
+    (a, b) if a.is_exact() && b.is_exact() => Val::rat(big::ratio(&a).unwrap() * big::ratio(&b).unwrap()),
     (a, b) => Num(a.as_c() * b.as_c()),
 });
-func!(a :div b => if b.as_c().im == 0. {
-    Num(a.as_c().unscale(b.as_c().re))
-} else {
-    Num(a.as_c().fdiv(b.as_c()))
+func!(a :div b => match (a, b) {
+    // fractions stay exact
+    #[cfg(feature="bigint")]
+    (a, b) if (matches!(a, Val::Rat(_)) && b.is_exact() || matches!(b, Val::Rat(_)) && a.is_exact()) && b.as_bool() =>
+        Val::rat(big::ratio(&a).unwrap() / big::ratio(&b).unwrap()),
+    (a, b) => if b.as_c().im == 0. {
+        Num(a.as_c().unscale(b.as_c().re))
+    } else {
+        Num(a.as_c().fdiv(b.as_c()))
+    }
+});
+// only dyadic: the monad of `_/` is `infloat`
+#[cfg(feature="bigint")]
+func!(@env, a :exact b => match (big::ratio(&a), big::ratio(&b)) {
+    (Some(a), Some(b)) if !b.is_zero() => Val::rat(a / b),
+    _ => div(env, a, Some(b)),
 });
+#[cfg(not(feature="bigint"))]
+func!(@env, a :exact b => div(env, a, Some(b)));
+func!(a :float => a.try_c().map_or(NAN, Num));
 func!(a :rem b => match (a, b) {
     (Int(a), Int(b)) => if b == 0 {NAN} else { Int(a.rem_euclid(b)) },
+    #[cfg(feature="bigint")]
+    (a, b) if a.is_integer() && b.is_integer() => {
+        let (a, b) = (a.bigint().unwrap(), b.bigint().unwrap());
+        if b.is_zero() {NAN} else { Val::big(big::rem_euclid(&a, &b)) }
+    },
+    #[cfg(feature="bigint")]
+    (a, b) if a.is_exact() && b.is_exact() => {
+        let (a, b) = (big::ratio(&a).unwrap(), big::ratio(&b).unwrap());
+        if b.is_zero() {NAN} else { Val::rat(big::divrem_ratio(&a, &b).1) }
+    },
     (a, b) => {
         let (a, b) = (a.as_c(), b.as_c());
         let mut r = a % b;
@@ -95,11 +150,34 @@ func!(a :rem b => match (a, b) {
         if r.im < 0.0 { r += b.im.abs(); }
     Num(r) },
 });
-intfunc!(a :dive b => if b == 0 {NAN} else { Int(a.div_euclid(b)) });
-func!(a :pow b => match (a, b) {
+func!(a :dive b => match (a.try_int(), b.try_int()) {
+    #[cfg(feature="bigint")]
+    _ if matches!(a, Val::Rat(_)) || matches!(b, Val::Rat(_)) => {
+        let (a, b) = (or_nan!(big::ratio(&a)), or_nan!(big::ratio(&b)));
+        if b.is_zero() {NAN} else { Val::big(big::divrem_ratio(&a, &b).0) }
+    },
+    #[cfg(feature="bigint")]
+    _ if a.is_big() || b.is_big() => {
+        let (a, b) = (or_nan!(big::int(&a)), or_nan!(big::int(&b)));
+        if b.is_zero() {NAN} else { Val::big(big::div_euclid(&a, &b)) }
+    },
+    (Some(a), Some(b)) => if b == 0 {NAN} else {
+        a.checked_div_euclid(b).map_or_else(|| promote!(a / b, i64::MAX), Int)
+    },
+    _ => NAN,
+});
+#[cfg(feature="bigint")]
+fn overflowed_pow(env: &super::Env, a: i64, b: u32) -> Val { big::pow(env, a.into(), b) }
+#[cfg(not(feature="bigint"))]
+fn overflowed_pow(_: &super::Env, a: i64, b: u32) -> Val { Num(c64::new(a as f64, 0.).powi(b as i32)) }
+func!(@env, a :pow b => match (a, b) {
     (Int(a), Int(b @ 0..=0x7FFFFFFF)) =>
         a.checked_pow(b as u32)
-        .map_or_else(|| Num(c64::new(a as f64, 0.).powi(b as i32)), Int),
+        .map_or_else(|| overflowed_pow(env, a, b as u32), Int),
+    #[cfg(feature="bigint")]
+    (Val::Big(a), Int(b @ 0..=0x7FFFFFFF)) => big::pow(env, (*a).c(), b as u32),
+    #[cfg(feature="bigint")]
+    (Val::Rat(a), Int(b @ -0x80000000..=0x7FFFFFFF)) => big::pow_ratio(env, (*a).c(), b as i32),
     (a, Int(b @ -0x80000000..=0x7FFFFFFF)) =>
         Num(a.as_c().powi(b as i32)),
     (a, b) => Num(a.as_c().powc(b.as_c())),
@@ -107,20 +185,24 @@ func!(a :pow b => match (a, b) {
 func!(a :log b => Num(a.as_c().log(b.as_c().norm())));
 func!(a :lt b => match (a, b) {
     (Int(a), Int(b)) => Val::bool(a < b),
+    (a, b) if a.is_bignum() && b.is_scalar() || b.is_bignum() && a.is_scalar() => Val::bool(a.cmpval(&b).is_lt()),
     (a, b) => a.try_c().zip(b.try_c()).map_or(NAN, |(a, b)| Val::bool(complexcmp(a, b).is_lt()))
 });
 func!(a :gt b => match (a, b) {
     (Int(a), Int(b)) => Val::bool(a > b),
+    (a, b) if a.is_bignum() && b.is_scalar() || b.is_bignum() && a.is_scalar() => Val::bool(a.cmpval(&b).is_gt()),
     (a, b) => a.try_c().zip(b.try_c()).map_or(NAN, |(a, b)| Val::bool(complexcmp(a, b).is_gt()))
 });
 func!(a :and b => Val::bool(a.as_bool() && b.as_bool()));
 func!(a :or  b => Val::bool(a.as_bool() || b.as_bool()));
 func!(a :max b => match (a, b) {
     (Int(a), Int(b)) => Int(a.max(b)),
+    (a, b) if a.is_bignum() || b.is_bignum() => if a.cmpval(&b).is_gt() {a} else {b},
     (a, b) => if complexcmp(a.as_c(), b.as_c()).is_gt() {a} else {b}
 });
 func!(a :min b => match (a, b) {
     (Int(a), Int(b)) => Int(a.min(b)),
+    (a, b) if a.is_bignum() || b.is_bignum() => if a.cmpval(&b).is_lt() {a} else {b},
     (a, b) => if complexcmp(a.as_c(), b.as_c()).is_lt() {a} else {b}
 });
 func!(a :atan2 b => {
@@ -129,9 +211,25 @@ func!(a :atan2 b => {
 });
 func!(a :approx b => Val::bool(Val::approx(&a, &b)));
 func!(a :isnan    => Val::bool(a.is_nan()));
-intfunc!(a :gcd b    => Int(num_integer::gcd(a, b)));
-intfunc!(a :lcm b    => Int(num_integer::lcm(a, b)));
-intfunc!(a :binom b  => Int(num_integer::binomial(a, b)));
+#[cfg(feature="bigint")]
+func!(a :gcd b => match (a, b) {
+    (Int(a), Int(b)) => Val::big(num_integer::gcd(BigInt::from(a), BigInt::from(b))),
+    (a, b) => big::int(&a).zip(big::int(&b)).map_or(NAN, |(a, b)| Val::big(num_integer::gcd(a, b))),
+});
+#[cfg(feature="bigint")]
+func!(a :lcm b => match (a, b) {
+    (Int(a), Int(b)) => Val::big(num_integer::lcm(BigInt::from(a), BigInt::from(b))),
+    (a, b) => big::int(&a).zip(big::int(&b)).map_or(NAN, |(a, b)| Val::big(num_integer::lcm(a, b))),
+});
+#[cfg(feature="bigint")]
+func!(@env, a :binom b => {
+    let (Some(n), Some(k)) = (big::int(&a), big::int(&b)) else { return NAN };
+    if k.is_negative() || k > n { return a.try_int().zip(b.try_int()).map_or(Int(0), |(a, b)| Int(num_integer::binomial(a, b))) }
+    big::binom(env, &n, &k)
+});
+#[cfg(not(feature="bigint"))] intfunc!(a :gcd b   => Int(num_integer::gcd(a, b)));
+#[cfg(not(feature="bigint"))] intfunc!(a :lcm b   => Int(num_integer::lcm(a, b)));
+#[cfg(not(feature="bigint"))] intfunc!(a :binom b => Int(num_integer::binomial(a, b)));
 intfunc!(a :band b   => Int(a & b));
 intfunc!(a :bor b    => Int(a | b));
 intfunc!(a :bxor b   => Int(a ^ b));
@@ -143,8 +241,18 @@ intfunc!(a :brepr    => Val::lis_fill(
         .collect(),
     Int(0)
 ));
-func!(a :abs   => match a { Int(a) => Int(a.abs()), Num(x) => Val::flt(x.norm()), _ => NAN });
-func!(a :neg   => match a { Int(a) => Int(-a),      Num(a) => Num(-a),            _ => NAN });
+func!(a :abs   => match a {
+    Int(a) => a.checked_abs().map_or_else(|| promote!(0 - a, i64::MAX), Int), Num(x) => Val::flt(x.norm()),
+    #[cfg(feature="bigint")] Val::Big(a) => Val::big(a.abs()),
+    #[cfg(feature="bigint")] Val::Rat(a) => Val::rat(a.abs()),
+    _ => NAN,
+});
+func!(a :neg   => match a {
+    Int(a) => a.checked_neg().map_or_else(|| promote!(0 - a, i64::MAX), Int), Num(a) => Num(-a),
+    #[cfg(feature="bigint")] Val::Big(a) => Val::big(-&*a),
+    #[cfg(feature="bigint")] Val::Rat(a) => Val::rat(-&*a),
+    _ => NAN,
+});
 func!(a :ln    => Num(a.as_c().ln()  ));
 func!(a :exp   => Num(a.as_c().exp() ));
 func!(a :sin   => Num(a.as_c().sin() ));
@@ -154,11 +262,21 @@ func!(a :acos  => Num(a.as_c().acos()));
 func!(a :tan   => Num(a.as_c().tan() ));
 func!(a :atan  => Num(a.as_c().atan()));
 func!(a :sqrt  => Num(a.as_c().sqrt()));
-func!(a :fact  => Val::flt(libm::tgamma(a.as_c().re + 1.)));
-func!(a :round => match a { Int(a) => Int(a), Num(a) => Val::flt(a.re.round()), _ => NAN });
-func!(a :ceil  => match a { Int(a) => Int(a), Num(a) => Val::flt(a.re.ceil()) , _ => NAN });
-func!(a :floor => match a { Int(a) => Int(a), Num(a) => Val::flt(a.re.floor()), _ => NAN });
-func!(a :sign  => match a { Int(a) => Int(a.signum()), Num(a) => {
+#[cfg(feature="bigint")]
+func!(@env, a :fact => match a { Int(n @ 0..) => big::fact(env, n), a => Val::flt(libm::tgamma(a.as_c().re + 1.)) });
+#[cfg(not(feature="bigint"))]
+func!(a :fact => match a {
+    Int(n @ 0..=20) => Int((1..=n).product()),
+    a => Val::flt(libm::tgamma(a.as_c().re + 1.)),
+});
+func!(a :round => match a { Int(a) => Int(a), Num(a) => Val::flt(a.re.round()), a if a.is_big() => a,
+    #[cfg(feature="bigint")] Val::Rat(a) => Val::big(a.round().to_integer()), _ => NAN });
+func!(a :ceil  => match a { Int(a) => Int(a), Num(a) => Val::flt(a.re.ceil()) , a if a.is_big() => a,
+    #[cfg(feature="bigint")] Val::Rat(a) => Val::big(a.ceil().to_integer()) , _ => NAN });
+func!(a :floor => match a { Int(a) => Int(a), Num(a) => Val::flt(a.re.floor()), a if a.is_big() => a,
+    #[cfg(feature="bigint")] Val::Rat(a) => Val::big(a.floor().to_integer()), _ => NAN });
+func!(a :sign  => match a { Int(a) => Int(a.signum()), #[cfg(feature="bigint")] Val::Big(a) => big::sign(&a),
+    #[cfg(feature="bigint")] Val::Rat(a) => big::sign(a.numer()), Num(a) => {
     if a == c64::new(0., 0.) { Int(0) } 
     else if a.im == 0. { Int(a.re.signum() as i64) }
     else if a.re == 0. { Num(c64::new(0., a.im.signum()))}
@@ -199,25 +317,28 @@ func!(@env, a :input b => {
     } else {
         buf = vec![0; chars as usize]; env.interface.read(stm, &mut buf)
     });
-    buf.into_iter().take(size).map(|i| Int(i64::from(i))).collect()
+    buf.truncate(size);
+    List::bytes(buf).into()
 });
-func!(a :fromutf8 => String::from_utf8_lossy(
+func!(a :fromutf8 => List::ints(String::from_utf8_lossy(
     &a.iterf().flat_map(|x| x.try_int().map(|x| (x & 0xff) as u8)).collect::<Vec<_>>()
-).chars().map(|x| Int(x as _)).collect());
-func!(a :toutf8 => a.iterf()
+).chars().map(|x| x as i64).collect()).into());
+func!(a :toutf8 => List::bytes(a.iterf()
     .flat_map(|x| x.try_int().map(|x| 
         x.try_into().ok().and_then(char::from_u32).unwrap_or('\u{FFFD}')))
     .collect::<String>()
-    .bytes()
-    .map(|x| Int(i64::from(x)))
-    .collect());
+    .into_bytes()).into());
 
-func!(@env, a :exit => if let Some(n) = a.try_int() {
-    Val::Err(n as i32)
+func!(@env, a :exit b? => if let Some(b) = b {
+    let code = b.try_int().map_or(1, |b| b as i32);
+    Error::new(ErrorKind::User, a.display_string()).code(code).prim("⌂").payload(a).into()
+} else if let Some(n) = a.try_int() {
+    Error::new(ErrorKind::Exit, "").code(n as i32).prim("⌂").into()
 } else {
-    _ = env.interface.write(0, a.display_string().as_bytes());
+    let message = a.display_string();
+    _ = env.interface.write(0, message.as_bytes());
     _ = env.interface.write(0, b"\n");
-    Val::Err(1)
+    Error::new(ErrorKind::Exit, message).prim("⌂").into()
 });
 func!(a :format b? => {
     let mut buf = String::new(); a.format(&mut buf,
@@ -232,7 +353,7 @@ func!(a :parse => if let Some(a @ 0x30..=0x39) = a.try_int() { Int(a - 0x30) } e
 
 func!(a :fromcp => { if a.is_nan() {return NAN}; a.try_int()
     .and_then(|x| u8::try_from(x).ok())
-    .map_or(NAN, |x| Int(
+    .map_or_else(|| Error::new(ErrorKind::Domain, "not a byte").prim("ë").into(), |x| Int(
         if x == b'\n' {'\n'} else {crate::codepage::tochar(x)}
     as i64))
 });
@@ -240,7 +361,7 @@ func!(a :tocp => { if a.is_nan() {return NAN}; a.try_int()
     .and_then(|x| u32::try_from(x).ok())
     .and_then(char::from_u32)
     .and_then(crate::codepage::tobyte)
-    .map_or(NAN, |x| Int(i64::from(x)))
+    .map_or_else(|| Error::new(ErrorKind::Domain, "not in the codepage").prim("ê").into(), |x| Int(i64::from(x)))
 });
 
 intfunc!(@env, a :pick b => {
@@ -269,4 +390,4 @@ func!(@env, a :call b => if b.is_scalar() {
 func!(@env, a :eval => 
     a.iterf().map(|x| x.try_int().and_then(|x| x.try_into().ok()) )
     .collect::<Option<Vec<u8>>>()
-    .map_or(NAN, |x| env.include_bytes(&x)));
\ No newline at end of file
+    .map_or_else(|| Error::new(ErrorKind::Type, "the code has to be bytes").prim("ò").into(), |x| env.include_bytes(&x)));
\ No newline at end of file
@@ -0,0 +1,50 @@
+use crate::prelude::*;
+use super::{Val, Env, Error, ErrorKind, Func, adverb::{self, AvT}, func::*, list::reverse};
+
+/// intrinsics that undo each other. β stays the same, so `α+β` is undone by `α-β`
+const FUNCS: &[(Func, Func)] = &[
+    (add, sub), (sub, add), (mul, div), (div, mul), (exp, ln), (ln, exp),
+    (neg, neg), (conj, conj), (bnot, bnot), (reverse, reverse),
+    (toutf8, fromutf8), (fromutf8, toutf8), (tocp, fromcp), (fromcp, tocp),
+];
+
+/// a function that undoes `g`: if `y` is `αgβ`, then `α` is `ygβ` with the inverse. functions
+/// defined in vemf are looked up in the `ininverses` dict of the stdlib
+pub fn inverse(env: &Env, g: &Val) -> Option<Val> {
+    if let Some(Val::Dict(d)) = env.get_var(b"ininverses") {
+        if let Some(i) = d.get(g) { return Some(i.c()) }
+    }
+    let av = |t: AvT, f: Option<Val>, g: Val| Val::Av(t, f.map(Val::rc), g.rc());
+    let is = |t: &AvT, u: AvT| *t as usize == u as usize;
+    match g {
+        Val::Func(f) => FUNCS.iter().find(|(x, _)| *f as usize == *x as usize).map(|(_, i)| Val::Func(*i)),
+        Val::Av(t, _, g) if is(t, adverb::undo) => Some((**g).c()),
+        // a valence without an inverse only fails when it's called
+        Val::Av(t, Some(f), g) if is(t, adverb::valences) => {
+            let (l, r) = (inverse(env, f), inverse(env, g));
+            if l.is_none() && r.is_none() { return None }
+            let undo = |x: &Rc<Val>| av(adverb::undo, None, (**x).c());
+            Some(av(*t, Some(l.unwrap_or_else(|| undo(f))), r.unwrap_or_else(|| undo(g))))
+        },
+        Val::Av(t, None, g) if is(t, adverb::conform) || is(t, adverb::each) || is(t, adverb::toleft) =>
+            Some(av(*t, None, inverse(env, g)?)),
+        Val::Av(t, Some(f), g) if is(t, adverb::bind) => Some(av(*t, Some((**f).c()), inverse(env, g)?)),
+        Val::Av(t, Some(f), g) if is(t, adverb::overright) => Some(av(*t, Some((**f).c()), inverse(env, g)?)),
+        Val::Av(t, Some(f), g) if is(t, adverb::atop) =>
+            Some(av(adverb::overleft, Some(inverse(env, g)?), inverse(env, f)?)),
+        Val::Av(t, Some(f), g) if is(t, adverb::overleft) =>
+            Some(av(adverb::atop, Some(inverse(env, g)?), inverse(env, f)?)),
+        // `g(fα)(fβ)` is undone by `f⁻¹(g⁻¹y(fβ))`
+        Val::Av(t, Some(f), g) if is(t, adverb::over) => {
+            let inner = av(adverb::overright, Some((**f).c()), inverse(env, g)?);
+            Some(av(adverb::atop, Some(inner), inverse(env, f)?))
+        },
+        _ => None,
+    }
+}
+
+/// like `inverse`, but it's an error if there isn't one
+pub fn inverse_or_err(env: &Env, g: &Val, prim: &'static str) -> Result<Rc<Val>, Val> {
+    inverse(env, g).map(Val::rc)
+        .ok_or_else(|| Error::new(ErrorKind::Inverse, "function has no inverse").prim(prim).into())
+}
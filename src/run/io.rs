@@ -34,6 +34,9 @@ pub trait Interface<'io> {
         }}
         Some(buf.len() - original_len)
     }
+    /// read a whole file, for `_ò`. `path` is the name given to it, joined to the directory of
+    /// the importing script or one of `Env::import_path`. return None if there's no such file
+    fn read_file(&mut self, _path: &str) -> Option<Vec<u8>> { None }
 }
 
 pub struct NoIO;
@@ -80,6 +83,7 @@ mod standard {
             1 => io_result(std::io::stderr().write(slice)),
             _ => None,
         }}
+        fn read_file(&mut self, path: &str) -> Option<Vec<u8>> { std::fs::read(path).ok() }
     }
 
     pub struct FromIoWrite<T: std::io::Write>(pub T);
@@ -1,41 +1,46 @@
 use crate::prelude::*;
 use iter::FusedIterator;
-use super::{Val::{self, Lis, Num, Int}, Env, NAN, adverb, func::left};
+use super::{Val::{self, Lis, Num, Int}, Env, NAN, List, Dict, Error, ErrorKind, Fallible, adverb, func::left};
 
 impl Val {
 
     #[allow(clippy::len_without_is_empty)] // shut up!!! shut up!!!!
     pub fn len(&self) -> usize { match self {
-        Num(_) | Int(_) => 1,
+        x if x.is_scalar() => 1,
         Lis { l, .. } => l.len(),
+        Val::Dict(d) => d.len(),
         _ => usize::MAX,
     }}
 
     pub fn fill(&self) -> Val { match self {
-        Num(_) | Int(_) => self.c(),
+        x if x.is_scalar() => self.c(),
         Lis { fill, .. } => (**fill).c(),
         _ => NAN, // good enough
     }}
 
     pub fn indexval(&self, env: &mut Env, index: Val) -> Val {
         match self {
-            Num(_) | Int(_) => self.c(), // unchanged
+            x if x.is_scalar() => self.c(), // unchanged
             Lis { l, fill } => 
                 if index.is_nan() {(**fill).c()} 
-                else if let Some(index) = index.try_int() {
+                else if matches!(index, Num(n) if n.re != n.re.trunc() || n.im != 0.0) {
+                    Error::new(ErrorKind::Index, "index isn't an integer").prim("@").into()
+                } else if let Some(index) = index.try_int() {
                     if index < 0 { return (**fill).c() }
-                    l.get(index as usize).cloned().unwrap_or_else(|| (**fill).c())
+                    l.item(index as usize).unwrap_or_else(|| (**fill).c())
                 } else {(**fill).c()},
+            Val::Dict(d) => d.get(&index).cloned().unwrap_or(NAN),
             x => x.monad(env, index)
         }
     }
 
     pub fn index(&self, env: &mut Env, index: usize) -> Val {
         match self {
-            Num(_) | Int(_) => self.c(), // unchanged
+            x if x.is_scalar() => self.c(), // unchanged
             Lis { l, fill } => {
-                l.get(index).cloned().unwrap_or_else(|| (**fill).c())
+                l.item(index).unwrap_or_else(|| (**fill).c())
             },
+            Val::Dict(d) => d.values().get(index).cloned().unwrap_or(NAN),
             x => x.monad(env, Int(index as i64))
         }
     }
@@ -49,14 +54,17 @@ impl Val {
     }
 
     pub fn lis_fill(vec: Vec<Val>, fill: Val) -> Val {
-        Lis{l: Rc::new(vec), fill: fill.rc()}
+        ok!(super::check_len(vec.len()));
+        Lis{l: Rc::new(vec.into()), fill: fill.rc()}
     }
 
     // create a finite iterator of `&Val`s. this returns a single item for functions, so it will 
-    // never be infinite AND it doesnt need a &mut env which is more convenient most of the time
+    // never be infinite AND it doesnt need a &mut env which is more convenient most of the time.
+    // dicts give their values
     pub fn iterf<'v>(&'v self) -> Box<dyn GoodIter<&'v Val> + 'v> {
         match self {
             Lis{l, ..} => bx(l.iter()),
+            Val::Dict(d) => bx(d.values().iter()),
             _ => bx(iter::once(self)),
         }
     }
@@ -64,9 +72,11 @@ impl Val {
     // like iterf but it clones its values EXCEPT if the vec has 1 ref then it drains them
     pub fn into_iterf(self) -> Box<dyn GoodIter<Val>> {
         if let Lis{l, ..} = self { match Rc::try_unwrap(l) {
-            Ok(l) => bx(l.into_iter()),
-            Err(l) => bx((0..l.len()).map(move |x| l[x].c()))
-        } } else { bx(iter::once(self)) }
+            Ok(l) => l.into_items(),
+            Err(l) => bx((0..l.len()).map(move |x| l.item(x).unwrap()))
+        } } else if let Val::Dict(d) = self {
+            bx(Dict::unwrap(d).into_values().into_iter())
+        } else { bx(iter::once(self)) }
     }
 
 }
@@ -82,9 +92,11 @@ func!(@env, a :index b => {
     let mut a = a;
     if b.is_nan() { return a.fill() }
     if a.is_infinite() { return a.monad(env, b) }
+    // the whole of β is the key
+    if let Val::Dict(d) = &a { return d.get(&b).cloned().unwrap_or(NAN) }
     for n in 0..b.len() {
         if a.is_scalar() { return a.c(); }
-        let i = b.index(env, n);
+        let i = ok!(b.index(env, n));
         if i.is_nan() {
             if n+1 == b.len() {return a}
             return match a {
@@ -95,7 +107,7 @@ func!(@env, a :index b => {
                 _ => iter::empty::<Val>().collect()
             };
         }
-        a = a.indexval(env, i);
+        a = ok!(a.indexval(env, i));
     }
     a
 });
@@ -115,9 +127,17 @@ impl<'r, 'io> Iterator for InfIter<'r, 'io> {
     fn size_hint(&self) -> (usize, Option<usize>) { (usize::MAX, None) }
 }
 
+// stops at the first error, so the rest of the iterator isn't evaluated
+pub fn collect_ok(iter: impl IntoIterator<Item = Val>) -> Result<Vec<Val>, Val> {
+    let iter = iter.into_iter();
+    let mut vec = Vec::with_capacity(iter.size_hint().0);
+    for x in iter { vec.push(x.into_result()?); }
+    Ok(vec)
+}
+
 impl FromIterator<Val> for Val {
     fn from_iter<T: IntoIterator<Item = Val>>(iter: T) -> Self {
-        Val::lis(iter.into_iter().collect())
+        collect_ok(iter).map_or_else(|e| e, Val::lis)
     }
 }
 
@@ -139,19 +159,25 @@ pub fn iota_scalar(arg: i64) -> Val {
     } else {
         bx((0..arg.abs()).rev())
     };
-    Val::lis(iter.map(Int).collect())
+    List::ints(iter.collect()).into()
 }
 
 func!(a :len => match a {
-    Num(_) | Int(_) => Int(1),
+    a if a.is_scalar() => Int(1),
     Lis { l, .. } => Int(l.len() as i64),
+    Val::Dict(d) => Int(d.len() as i64),
     _ => Val::flt(f64::INFINITY),
 });
-func!(a :iota => match a {
-    Lis{l, ..} => Val::lis(iiota(
-        Vec::new(), &l.iter().cloned().filter_map(|x| x.try_int()).collect::<Vec<i64>>())),
-    Num(n) => if n.is_infinite() {Val::Func(left)} else {iota_scalar(n.re as i64)},
-    Int(n) => iota_scalar(n),
+func!(@env, a :iota => match a {
+    Lis{l, ..} => {
+        let shape = l.iter().cloned().filter_map(|x| x.try_int()).collect::<Vec<i64>>();
+        ok!(env.check_alloc(shape.iter().fold(1, |a, x| a.saturating_mul(x.unsigned_abs() as usize))));
+        Val::lis(iiota(Vec::new(), &shape))
+    },
+    Num(n) => if n.is_infinite() {Val::Func(left)} else {
+        ok!(env.check_alloc((n.re as i64).unsigned_abs() as usize)); iota_scalar(n.re as i64)
+    },
+    Int(n) => { ok!(env.check_alloc(n.unsigned_abs() as usize)); iota_scalar(n) },
     _ => Val::Av(adverb::constant, None, NAN.rc()),
 });
 func!(a :pair b => Val::lis(vec![a, b]));
@@ -168,13 +194,15 @@ pub fn vecravel(arg: Val, list: &mut Vec<Val>) {
     }
 }
 
-func!(a :replist b => if !a.is_infinite() {
-    (0..or_nan!(b.try_int())).flat_map(|_| a.iterf().cloned()).collect()
+func!(@env, a :replist b => if !a.is_infinite() {
+    let n = or_nan!(b.try_int());
+    ok!(env.check_alloc(a.len().saturating_mul(n.max(0) as usize)));
+    (0..n).flat_map(|_| a.iterf().cloned()).collect()
 } else {a});
 
 func!(a :concat b => {
     if let Lis{l, ..} = a { return match Rc::try_unwrap(l) {
-        Ok(mut l) => { l.extend(b.iterf().cloned()); Val::lis(l) },
+        Ok(l) => { let mut l = l.into_vec(); l.extend(b.into_iterf()); Val::lis(l) },
         Err(l) => l.iter().cloned().chain(b.into_iterf()).collect()
     }}
     a.into_iterf().chain(b.into_iterf()).collect()
@@ -189,18 +217,19 @@ func!(@env, a :takeleft  b => reshape(env, a, b, false));
 func!(@env, a :takeright b => reshape(env, a, b, true));
 
 pub fn reshape(env: &mut Env, a: Val, b: Val, isright: bool) -> Val {
-    if b.is_infinite() { return NAN };
+    let prim = if isright {"↓"} else {"↑"};
+    if b.is_infinite() { return Error::new(ErrorKind::Type, "β can't be a function").prim(prim).into() };
     let mut shape = Vec::<usize>::new();
     let mut spot = None::<usize>; // in 2■↑, the spot is Some(1)
-    let mut product = if isright {-1} else {1};
+    let mut product: isize = if isright {-1} else {1};
     for i in b.iterf() { 
         if i.is_nan() && spot.is_none() { // there can only be 1 spot 
             spot = Some(shape.len());
             shape.push(1);
         } else if let Some(n) = i.try_int() {
-            product *= n as isize;
+            product = product.saturating_mul(n as isize);
             shape.push(n.unsigned_abs() as usize);
-        } else { return NAN }
+        } else { return Error::new(ErrorKind::Type, "the shape has to be integers").prim(prim).into() }
     }
     // fill up the spot
     if let Some(index) = spot {
@@ -212,8 +241,9 @@ pub fn reshape(env: &mut Env, a: Val, b: Val, isright: bool) -> Val {
             product.unsigned_abs(),
         );
         shape[index] = num;
-        product *= num as isize;
+        product = product.saturating_mul(num as isize);
     }
+    ok!(env.check_alloc(shape.iter().fold(1, |a: usize, x| a.saturating_mul(*x))));
     let fill = a.fill();
     let mut iter = if product >= 0 {
         // pick from left
@@ -238,11 +268,13 @@ pub fn ireshape(a: &mut dyn Iterator<Item=Val>, b: &[usize], fill: &Val) -> Val
     (0..pre).map(move |_| ireshape(a, suf, fill)).collect()
 }
 
-func!(a :dropleft  b => b.try_int().map_or(NAN, |b| drop(a, b)));
-func!(a :dropright b => b.try_int().map_or(NAN, |b| drop(a, -b)));
+func!(a :dropleft  b => b.try_int().map_or_else(|| drop_err("β isn't an integer", "♂"), |b| drop(a, b)));
+func!(a :dropright b => b.try_int().map_or_else(|| drop_err("β isn't an integer", "♀"), |b| drop(a, -b)));
+
+fn drop_err(msg: &str, prim: &'static str) -> Val { Error::new(ErrorKind::Type, msg).prim(prim).into() }
 
 pub fn drop(a: Val, b: i64) -> Val {
-    if a.is_infinite() { return NAN; }
+    if a.is_infinite() { return drop_err("α can't be a function", if b >= 0 {"♂"} else {"♀"}); }
     if b >= 0 {
         a.into_iterf().skip(b as _).collect()
     } else {
@@ -257,7 +289,7 @@ func!(a :shape b => Val::lis_fill(
 pub fn ishape(a: &Val, lim: i64) -> Vec<usize> {
     if !a.is_list() { return vec![]; };
     let mut shp = vec![a.len()];
-    if lim == 0 { return shp; }
+    if lim == 0 || matches!(a, Lis{l, ..} if l.is_packed()) { return shp; }
     //if lim < 64 {shp.resize(lim as _, 0)}
     for v in a.iterf() {
         let inr = ishape(v, lim-1);
@@ -269,49 +301,77 @@ pub fn ishape(a: &Val, lim: i64) -> Vec<usize> {
     shp
 }
 
-func!(@env, a :replicate b => { let fill = a.fill();
-    Val::lis_fill(ireplicate(env, a, b), fill)
+func!(@env, a :replicate b => if let Val::Dict(d) = &a {
+    // entries can't be repeated, so they're kept once if their count is positive
+    let mut dict = Dict::default();
+    for ((k, v), n) in d.iter().zip(b.iterinf(env)) {
+        if ok!(n).try_int().is_some_and(|n| n > 0) { dict.insert(k.c(), v.c()); }
+    }
+    dict.into()
+} else { let fill = a.fill();
+    Val::lis_fill(ok!(ireplicate(env, a, b)), fill)
 });
 
-pub fn ireplicate(env: &mut Env, a: Val, b: Val) -> Vec<Val> {
+pub fn ireplicate(env: &mut Env, a: Val, b: Val) -> Result<Vec<Val>, Val> {
     let mut lis = Vec::new();
     let afill = a.fill();
     for (l,r) in a.into_iterf().zip(b.iterinf(env)).collect::<Vec<_>>().into_iter() {
+        let r = r.into_result()?;
         if let Some(n) = r.try_int() { if n != 0 {
+            env.check_alloc(lis.len().saturating_add(n.unsigned_abs() as usize))?;
             let val = if n > 0 {l} else {afill.c()};
             for _ in 0..(n.abs() - 1) { lis.push(val.c()); }
             lis.push(val);
         }} else {
-            lis.extend(ireplicate(env, l, r).into_iter());
+            lis.extend(ireplicate(env, l, r)?.into_iter());
         }
     }
-    lis
+    Ok(lis)
 }
 
 func!(a :gradeup => if let Lis {l, ..} = a {
     let mut lis = (0..l.len()).collect::<Vec<_>>();
     lis.sort_by(|&a, &b| l[a].cmpval(&l[b]));
     Val::lis(lis.into_iter().map(|x| Int(x as i64)).collect())
+} else if let Val::Dict(d) = a {
+    Val::lis(sort_entries(&d, false).into_iter().map(|x| x.0).collect())
 } else { Val::lis(vec![Int(0)]) });
 
 func!(a :gradedown => if let Lis {l, ..} = a {
     let mut lis = (0..l.len()).collect::<Vec<_>>();
     lis.sort_by(|&a, &b| l[a].cmpval(&l[b]).reverse());
     Val::lis(lis.into_iter().map(|x| Int(x as i64)).collect())
+} else if let Val::Dict(d) = a {
+    Val::lis(sort_entries(&d, true).into_iter().map(|x| x.0).collect())
 } else { Val::lis(vec![Int(0)]) } );
 
 func!( a :sortup => if let Lis {l, ..} = a {
-    let mut list = Rc::try_unwrap(l).unwrap_or_else(|x| (*x).c());
+    if let List::Ints(v, _) = &*l { let mut v = v.c(); v.sort_unstable(); return List::ints(v).into() }
+    let mut list = List::unwrap(l);
     list.sort_by(|a, b| a.cmpval(b));
     Val::lis(list)
+} else if let Val::Dict(d) = a {
+    sort_entries(&d, false).into_iter().collect::<Dict>().into()
 } else { Val::lis(vec![a]) } );
 
 func!(a :sortdown => if let Lis {l, ..} = a {
-    let mut list = Rc::try_unwrap(l).unwrap_or_else(|x| (*x).c());
+    if let List::Ints(v, _) = &*l {
+        let mut v = v.c(); v.sort_unstable_by(|a, b| b.cmp(a)); return List::ints(v).into()
+    }
+    let mut list = List::unwrap(l);
     list.sort_by(|a, b| a.cmpval(b).reverse());
     Val::lis(list)
+} else if let Val::Dict(d) = a {
+    sort_entries(&d, true).into_iter().collect::<Dict>().into()
 } else { Val::lis(vec![a]) });
 
+// the entries of a dictionary, ordered by their values
+fn sort_entries(d: &Dict, down: bool) -> Vec<(Val, Val)> {
+    let mut entries = d.iter().map(|(k, v)| (k.c(), v.c())).collect::<Vec<_>>();
+    entries.sort_by(|a, b| if down { a.1.cmpval(&b.1).reverse() } else { a.1.cmpval(&b.1) });
+    entries
+}
+
 func!(a :binsup b => if let Lis {l, ..} = a {
     Int(l.partition_point(|x| x.cmpval(&b).is_le()) as i64)
 } else { Int(0) } );
@@ -321,11 +381,11 @@ func!( a :binsdown b => if let Lis {l, ..} = a {
 } else { Int(0) });
 
 func!( @env, a :group b => {
-    if a.is_infinite() { return NAN; }
+    if a.is_infinite() { return Error::new(ErrorKind::Type, "α can't be a function").prim("ü").into(); }
     let mut lis: Vec<Vec<Val>> = Vec::new();
     let len = a.len();
     for (l, r) in a.into_iterf().zip(b.iterinf(env).take(len)) {
-        for i in r.into_iterf() {
+        for i in ok!(r).into_iterf() {
             if i.is_nan() { continue }
             // epic type inference fail
             if let Some(i) = i.try_int().and_then(|x|->Option<usize> {x.try_into().ok()}) {
@@ -341,8 +401,8 @@ func!( @env, a :group b => {
 });
 
 func!(a :find b => {
-    let Lis{l: a, ..} = a else { return NAN };
-    let b = match b { Lis{l, ..} if l.len() != 0 => l, _ => Rc::new(vec![b]) };
+    let Lis{l: a, ..} = a else { return Error::new(ErrorKind::Type, "α isn't a list").prim("£").into() };
+    let b = match b { Lis{l, ..} if l.len() != 0 => l, _ => Rc::new(vec![b].into()) };
     let mut res = vec![NAN; a.len()];
     let mut i = 0;
     while i+b.len() <= a.len() {
@@ -366,14 +426,47 @@ func!(a :occcount => {
 });
 
 func!(a :uio b => {
-    let mut used_matches = vec![false; a.len()];
-    b.into_iterf().map(|i| {
-        for (n, j) in a.iterf().enumerate() { if !used_matches[n] && i == *j {
-            used_matches[n] = true;
-            return Int(n as _)
-        }}
-        NAN
-    }).collect()
+    // the indices of each item, in reverse so the first unused one can be popped
+    let mut map: HashMap<&Val, Vec<usize>> = HashMap::with_capacity(a.len());
+    for (n, i) in a.iterf().enumerate().rev() { map.entry(i).or_default().push(n); }
+    b.iterf().map(|i| map.get_mut(i).and_then(Vec::pop).map_or(NAN, |n| Int(n as i64))).collect()
+});
+
+func!(a :indexof b => {
+    let mut map = HashMap::with_capacity(a.len());
+    for (n, i) in a.iterf().enumerate() { map.entry(i).or_insert(n); }
+    let find = |x: &Val| map.get(x).map_or(NAN, |&n| Int(n as i64));
+    if b.is_scalar() { find(&b) } else { b.iterf().map(find).collect() }
+});
+
+func!(a :member b => {
+    if a.is_infinite() { return Val::Fork(a.rc(), Val::Func(member).rc(), b.rc()) }
+    let set = b.iterf().collect::<HashSet<_>>();
+    if a.is_scalar() { return Val::bool(set.contains(&a)) }
+    let fill = a.fill();
+    let fill = if fill.is_nan() { NAN } else { Val::bool(set.contains(&fill)) };
+    Val::lis_fill(a.iterf().map(|x| Val::bool(set.contains(x))).collect(), fill)
+});
+
+func!(a :nubsieve => {
+    let mut seen = HashSet::with_capacity(a.len());
+    List::ints(a.iterf().map(|x| i64::from(seen.insert(x))).collect()).into()
+});
+
+func!(a :intersect b => {
+    let set = b.iterf().collect::<HashSet<_>>();
+    Val::lis_fill(a.iterf().filter(|x| set.contains(x)).cloned().collect(), a.fill())
+});
+
+func!(a :difference b => {
+    let set = b.iterf().collect::<HashSet<_>>();
+    Val::lis_fill(a.iterf().filter(|x| !set.contains(x)).cloned().collect(), a.fill())
+});
+
+func!(a :union b => {
+    let set = a.iterf().collect::<HashSet<_>>();
+    let items = a.iterf().chain(b.iterf().filter(|x| !set.contains(x))).cloned().collect();
+    Val::lis_fill(items, a.fill())
 });
 
 func!(a :domainto b => {
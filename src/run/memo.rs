@@ -0,0 +1,63 @@
+use core::cell::RefCell;
+use alloc::collections::VecDeque;
+use crate::prelude::*;
+use super::{Val, Env, NAN, code::dfn_frame};
+
+type Args = (Val, Option<Val>);
+
+/// a function that remembers its results. a memoized dfn gets itself as `ƒ`, so its recursive
+/// calls are remembered too
+pub struct Memo {
+    pub f: Val,
+    /// how many results are kept. the oldest ones are forgotten first
+    limit: usize,
+    cache: RefCell<HashMap<Args, Val>>,
+    order: RefCell<VecDeque<Args>>,
+}
+
+impl Memo {
+    /// memoizing a memoized function starts over with an empty cache
+    pub fn wrap(f: Val, limit: usize) -> Val {
+        let f = if let Val::Memo(m) = f { m.f.c() } else { f };
+        Val::Memo(Rc::new(Memo { f, limit, cache: RefCell::default(), order: RefCell::default() }))
+    }
+
+    /// forget every result, for everything that shares this memo
+    pub fn clear(&self) {
+        self.cache.borrow_mut().clear();
+        self.order.borrow_mut().clear();
+    }
+
+    pub fn call(&self, this: &Val, env: &mut Env, a: Val, b: Option<Val>) -> Val {
+        let args = (a, b);
+        if let Some(x) = self.cache.borrow().get(&args) { return x.c() }
+        if self.limit == 0 { return self.f.call(env, args.0, args.1) }
+        let (a, b) = args.c();
+        let x = match &self.f {
+            Val::Dfn { code, loc, .. } => {
+                env.stack.push(dfn_frame(this, loc, a, b));
+                let x = env.exec(code);
+                env.stack.pop();
+                x
+            },
+            f => f.call(env, a, b),
+        };
+        if x.is_err() { return x }
+        let mut cache = self.cache.borrow_mut();
+        if self.limit != usize::MAX {
+            let mut order = self.order.borrow_mut();
+            if cache.len() >= self.limit {
+                if let Some(old) = order.pop_front() { cache.remove(&old); }
+            }
+            order.push_back(args.c());
+        }
+        cache.insert(args, x.c());
+        x
+    }
+}
+
+// these are bound to adverbs, so they get the function to memoize as α. `_╤` doesn't take a
+// limit, even if it's called with one
+func!(a :memo => Memo::wrap(a, usize::MAX));
+func!(a :memolimit b => Memo::wrap(a, b.try_int().map_or(usize::MAX, |x| x.max(0) as usize)));
+func!(a :memoclear => { if let Val::Memo(m) = &a { m.clear(); } a });
@@ -1,22 +1,90 @@
-#[macro_use] mod func; mod list; mod adverb; mod disp; mod val; pub mod io;
+#[macro_use] mod func; mod list; mod array; mod adverb; mod disp; mod val; mod error; mod code; mod dict; mod inverse; mod memo; pub mod io;
+#[cfg(feature="bigint")] mod big;
 
-use crate::parse::{Expr, Stmt};
+use crate::token::Span;
 use crate::prelude::*;
 use adverb::AvT;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 const STDLIB: &str = include_str!("../std.vemf");
 
 pub use num_complex::Complex64 as c64;
+pub use error::{Error, ErrorKind, TraceFrame};
+pub use array::List;
+pub use code::Code;
+pub use dict::Dict;
 
 pub const NAN: Val = Num(c64::new(f64::NAN, f64::NAN));
 
-pub type Frame = HashMap<Bstr, Val>;
+/// the variables of a scope. the ones the running code knows about are in slots, and the
+/// rest (defined with `get`, `eval` and such) are looked up by name
+#[derive(Clone, Default)]
+pub struct Frame {
+    pub slots: Vec<Option<Val>>,
+    pub names: Rc<[Bstr]>,
+    pub vars: HashMap<Bstr, Val>,
+}
+
+impl Frame {
+    pub fn new(names: Rc<[Bstr]>) -> Frame {
+        Frame { slots: vec![None; names.len()], names, vars: HashMap::new() }
+    }
+
+    fn slot(&self, name: &[u8]) -> Option<usize> { self.names.iter().position(|x| x[..] == *name) }
+
+    pub fn get(&self, name: &[u8]) -> Option<&Val> { match self.slot(name) {
+        Some(i) => self.slots[i].as_ref(),
+        None => self.vars.get(name),
+    }}
+
+    pub fn insert(&mut self, name: Bstr, value: Val) { match self.slot(&name) {
+        Some(i) => self.slots[i] = Some(value),
+        None => { self.vars.insert(name, value); },
+    }}
+
+    pub fn remove(&mut self, name: &[u8]) -> Option<Val> { match self.slot(name) {
+        Some(i) => self.slots[i].take(),
+        None => self.vars.remove(name),
+    }}
+}
 
 /// vemf interpreter state
 pub struct Env<'io> {
     pub stack: Vec<Frame>,
+    /// the standard library, shared between envs. names not found in the stack are looked up here
+    pub stdlib: Option<Rc<Frame>>,
     pub rng: Box<dyn rand::RngCore>,
     pub interface: Box<dyn io::Interface<'io> + 'io>,
+    /// record the functions an error goes through. off by default, since it slows down calls
+    pub trace: bool,
+    /// how many more evaluation steps are allowed, if limited
+    pub fuel: Option<u64>,
+    /// stop evaluating as soon as this is set. can be shared with other threads
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// how many calls deep the running code is
+    pub depth: usize,
+    /// maximum number of nested calls, counting the calls adverbs make and going into nested
+    /// lists. the default needs about 64MB of stack in release builds, more than most main
+    /// threads have, so the binary runs on a thread with a bigger one
+    pub max_depth: usize,
+    /// maximum length of a list created by iota, reshape and such
+    pub max_alloc: usize,
+    /// directory of the running script, where `_ò` looks for files first. empty for the
+    /// working directory
+    pub dir: String,
+    /// other directories `_ò` looks in, in order
+    pub import_path: Vec<String>,
+    /// files imported so far, by path. None while a file is being imported
+    pub modules: HashMap<String, Option<Rc<Module>>>,
+}
+
+/// what importing a file leaves behind
+pub struct Module {
+    /// the value of the last statement
+    pub value: Val,
+    /// the variables it defined
+    pub vars: HashMap<Bstr, Val>,
 }
 
 #[macro_export]
@@ -27,6 +95,26 @@ macro_rules! or_nan { ($expr:expr) => {
     }
 } }
 
+/// early-return errors, for `Val`s or `Result`s of them
+#[macro_export]
+macro_rules! ok { ($expr:expr) => {
+    match $crate::run::Fallible::into_result($expr) {
+        Ok(x) => x,
+        Err(e) => return e,
+    }
+} }
+
+/// anything `ok!` can short-circuit on
+pub trait Fallible { type Ok; fn into_result(self) -> Result<Self::Ok, Val>; }
+impl Fallible for Val {
+    type Ok = Val;
+    fn into_result(self) -> Result<Val, Val> { if self.is_err() { Err(self) } else { Ok(self) } }
+}
+impl<T> Fallible for Result<T, Val> {
+    type Ok = T;
+    fn into_result(self) -> Result<T, Val> { self }
+}
+
 pub type Func = fn(env: &mut Env, a: Val, b: Option<Val>) -> Val;
 
 /// represents a vemf value
@@ -34,13 +122,22 @@ pub type Func = fn(env: &mut Env, a: Val, b: Option<Val>) -> Val;
 pub enum Val {
     Num(c64),
     Int(i64),
-    Lis { l: Rc<Vec<Val>>, fill: Rc<Val> },
+    /// an integer too big for an i64
+    #[cfg(feature="bigint")] Big(Rc<num_bigint::BigInt>),
+    /// an exact fraction that isn't an integer
+    #[cfg(feature="bigint")] Rat(Rc<num_rational::BigRational>),
+    Lis { l: Rc<List>, fill: Rc<Val> },
+    Dict(Rc<Dict>),
     FSet(Bstr), FCng(Bstr),
-    Dfn { loc: Rc<HashMap<Bstr, Val>>, s: Rc<[Stmt]> },
+    /// assigns the parts of its argument
+    FPat(Rc<crate::parse::Pat>),
+    Dfn { loc: Rc<Frame>, code: Rc<Code>, span: Span },
     Fork(Rc<Val>, Rc<Val>, Rc<Val>),
+    /// a function with a cache of its results
+    Memo(Rc<memo::Memo>),
     Av(AvT, Option<Rc<Val>>, Rc<Val>),
     AvBuilder(AvT),
-    Err(i32), // exit code
+    Err(Rc<Error>),
     Func(Func)
 }
 
@@ -53,11 +150,46 @@ impl Default for Val {
 impl<'io> Env<'io> {
     
     pub fn new<'a>(rng: Box<dyn rand::RngCore>) -> Env<'a> {
-        Env::from_frame(HashMap::new(), rng)
+        Env::from_frame(Frame::default(), rng)
     }
 
     pub fn from_frame<'a>(frame: Frame, rng: Box<dyn rand::RngCore>) -> Env<'a> {
-        Env { stack: vec![frame], interface: bx(io::NoIO), rng }
+        Env {
+            stack: vec![frame], stdlib: None, interface: bx(io::NoIO), rng,
+            trace: false, fuel: None, cancel: None,
+            depth: 0, max_depth: 10000, max_alloc: 1 << 26,
+            dir: String::new(), import_path: Vec::new(), modules: HashMap::new(),
+        }
+    }
+
+    /// fail if a list of `len` items would be too big
+    pub fn check_alloc(&self, len: usize) -> Result<(), Val> {
+        if len > self.max_alloc { return Err(Error::new(ErrorKind::Limit, "list too big").into()) }
+        Ok(())
+    }
+
+    /// run `f` a level deeper, failing if that goes past `max_depth`
+    pub fn deeper(&mut self, f: impl FnOnce(&mut Self) -> Val) -> Val {
+        if self.depth >= self.max_depth { return Error::new(ErrorKind::Limit, "too much recursion").into() }
+        // calls from outside of any code have to set the limit themselves
+        let old = (self.depth == 0).then(|| set_max_alloc(self.max_alloc));
+        self.depth += 1;
+        let val = f(self);
+        self.depth -= 1;
+        if let Some(old) = old { set_max_alloc(old); }
+        val
+    }
+
+    /// take a step, failing if out of fuel or cancelled
+    pub fn tick(&mut self) -> Result<(), Val> {
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 { return Err(Error::new(ErrorKind::Fuel, "").into()) }
+            *fuel -= 1;
+        }
+        if self.cancel.as_ref().is_some_and(|x| x.load(Ordering::Relaxed)) {
+            return Err(Error::new(ErrorKind::Cancelled, "").into())
+        }
+        Ok(())
     }
 
     pub fn locals(&self) -> &Frame { self.stack.last().unwrap() }
@@ -71,7 +203,7 @@ impl<'io> Env<'io> {
         if let Some(b!('→')) = name.first() {name = &name[1..]}
         let mut skipped = 0;
         loop {
-            for frame in self.stack.iter().rev().skip(skipped) {
+            for frame in self.stack.iter().rev().skip(skipped).chain(self.stdlib.as_deref()) {
                 if let Some(var) = frame.get(name) {
                     return Some(var.c())
                 }
@@ -86,7 +218,7 @@ impl<'io> Env<'io> {
     pub fn get_var(&self, mut name: &[u8]) -> Option<Val> {
         let mut skipped = 0;
         loop {
-            for frame in self.stack.iter().rev().skip(skipped) {
+            for frame in self.stack.iter().rev().skip(skipped).chain(self.stdlib.as_deref()) {
                 if let Some(var) = frame.get(name) {
                     return Some(var.c())
                 }
@@ -102,12 +234,18 @@ impl<'io> Env<'io> {
         let mut skipped = 0;
         loop {
             for (fmn, frame) in self.stack.iter_mut().enumerate().rev().skip(skipped) {
-                if let Some((nam, val)) = frame.remove_entry(name) {
+                if let Some(val) = frame.remove(name) {
                     let val = func.call(self, val, b);
-                    self.stack[fmn].insert(nam, val.c());
+                    self.stack[fmn].insert(Bstr::from(name), val.c());
                     return Some(val);
                 }
             }
+            // the stdlib is shared, so its variables are changed in the bottom frame instead
+            if let Some(val) = self.stdlib.as_ref().and_then(|x| x.get(name)) {
+                let val = func.call(self, val.c(), b);
+                self.stack[0].insert(Bstr::from(name), val.c());
+                return Some(val);
+            }
             if let Some(b!('←')) = name.first() {
                 name = &name[1..];
                 skipped += 1;
@@ -118,9 +256,15 @@ impl<'io> Env<'io> {
     pub fn delete_var(&mut self, mut name: &[u8]) {
         let mut skipped = 0;
         loop {
-            for frame in self.stack.iter_mut().rev().skip(skipped) {
-                if frame.remove_entry(name).is_some() { return; }
+            let found = self.stack.iter_mut().enumerate().rev().skip(skipped)
+                .find_map(|(i, frame)| frame.remove(name).map(|_| i));
+            if found.is_some_and(|i| i > 0) { return }
+            // the bottom frame acts as if it held the stdlib too. only this env stops seeing the
+            // variable, so the stdlib gets copied the first time
+            if let Some(std) = self.stdlib.as_mut().filter(|x| x.get(name).is_some()) {
+                Rc::make_mut(std).remove(name); return
             }
+            if found.is_some() { return }
             if let Some(b!('←')) = name.first() {
                 name = &name[1..];
                 skipped += 1;
@@ -128,119 +272,72 @@ impl<'io> Env<'io> {
         }
     }
 
-    pub fn eval(&mut self, expr: &Expr) -> Val {
-        macro_rules! eval { ($v:expr) => {
-            match self.eval($v) {
-                Val::Err(x) => return Val::Err(x),
-                x => x,
-            }
-        }}
-        match expr {
-            Expr::Var(s) => self.get_var(s).unwrap_or_default(),
-            Expr::Int(n) => Int(*n),
-            Expr::Flt(n) => Num(*n),
-            Expr::Snd(l) => {    
-                let mut v = Vec::with_capacity(l.len());
-                for x in l { v.push(eval!(x)); }
-                Lis { l: Rc::from(v), fill: NAN.rc() }
-            },
-            Expr::Afn1(a, f) => {
-                let a = eval!(a); let f = eval!(f);
-                f.monad(self, a)
-            },
-            Expr::Afn2(a, f, b) => {
-                let a = eval!(a); let f = eval!(f); 
-                if let Val::FSet(_) = f { f.monad(self, a); return eval!(b) }
-                let b = eval!(b);
-                f.dyad(self, a, b)
-            },
-            Expr::SetVar(v) => Val::FSet(v.c()),
-            Expr::MutVar(v) => Val::FCng(v.c()),
-            Expr::Aav1(v, g) => {
-                let g = eval!(&g.c());
-                self.get_var(&v[..]).unwrap_or_default().monad(self, g)
-            }
-            Expr::Aav2(f, v, g) => {
-                let f = eval!(&f.c()); let g = eval!(&g.c());
-                self.get_var(&v[..]).unwrap_or_default().dyad(self, g, f)
-            },
-            Expr::Bind(f, b) => {
-                let f = eval!(&f.c()); let b = eval!(&b.c());
-                Val::bind(f.rc(), b.rc())
-            },
-            Expr::Trn2(a, f) => {
-                let a = eval!(&a.c()); let f = eval!(&f.c());
-                Val::atop(a.rc(), f.rc())
-            },
-            Expr::Trn3(a, f, b) => {
-                let a = eval!(&a.c()); let f = eval!(&f.c()); let b = eval!(&b.c());
-                Val::atop(a.rc(), Val::bind(f.rc(), b.rc()).rc())
-            },
-            Expr::Fork(a, f, b) => {
-                let a = eval!(&a.c()); let f = eval!(&f.c()); let b = eval!(&b.c());
-                Val::Fork(a.rc(), f.rc(), b.rc())
-            },
-            Expr::Dfn { s, cap } => {
-                let mut locals = HashMap::with_capacity(cap.len());
-                for var in cap {
-                    self.get_var_cap(var).and_then(|x| locals.insert(var.c(), x));
-                }
-                Val::Dfn{s: Rc::from(&s[..]), loc: Rc::new(locals)}
-            },
-            Expr::Block(s) => self.eval_block(s)
-        }
-    }
-
-    pub fn eval_stmt(&mut self, stmt: &Stmt) -> Option<Val> {
-        macro_rules! eval { ($v:expr) => {
-            match self.eval($v) {
-                Val::Err(x) => return Some(Val::Err(x)),
-                x => x,
-            }
-        }}
-        match stmt {
-            Stmt::Discard(expr) => { _ = eval!(expr); },
-            Stmt::Loc(a, v) => { let a = eval!(a); self.set_local(v.c(), a); },
-            Stmt::Mut(a, v) => { let a = eval!(a); self.mutate_var(v, a, None); },
-            Stmt::DelLoc(v) => { self.locals_mut().remove(v); },
-            Stmt::DelMut(v) => { self.delete_var(v); },
-            Stmt::Return(expr) => { return Some(eval!(expr)); },
-            Stmt::Cond(cond, then) => {
-                let val = eval!(cond);
-                let cond = val.is_scalar() && val.as_bool() || {
-                    let a = self.locals().get(&[b!('α')][..]).cloned().unwrap_or(NAN);
-                    let b = self.locals().get(&[b!('Σ')][..])
-                        .map_or(false, |x| x.try_int() != Some(1))
-                        .then(|| self.locals().get(&[b!('β')][..]).cloned().unwrap_or(NAN) );
-                    val.call(self, a, b).as_bool()
-                };
-                if cond { return self.eval_stmt(then) }
-            }
-        };
-        None
-    }
-
-    pub fn eval_block(&mut self, block: &[Stmt]) -> Val {
-        for stmt in block.iter() { 
-            if let Some(val) = self.eval_stmt(stmt) { return val };
-        }
-        NAN
-    }
     pub fn include_string(&mut self, code: &str) -> Val {
-        self.include_bytes(&crate::codepage::tobytes(code).unwrap()[..])
+        match tobytes(code) { Ok(code) => self.include_bytes(&code), Err(e) => Val::Err(e) }
     }
     pub fn include_bytes(&mut self, code: &[u8]) -> Val {
         use crate::{token, parse};
         let tokens = token::tokenize(code);
         //println!("{:?}", tokens);
-        let parsed = parse::parse(&tokens);
+        let parsed = match parse::parse(&tokens) {
+            Ok(parsed) => parsed,
+            Err(e) => return Error::new(ErrorKind::Syntax, e.describe(code)).at(e.span).into(),
+        };
         //for i in &parsed { println!("parsed: {i}"); }
-        self.eval_block(&parsed)
+        let old = set_max_alloc(self.max_alloc);
+        let val = self.exec(&code::compile(parsed));
+        set_max_alloc(old);
+        val
     }
 
+    /// make the standard library visible under every frame
     pub fn include_stdlib(&mut self) {
-        func::load_intrinsics(self);
-        self.include_string(STDLIB);
+        self.stdlib = Some(self.stdlib.take().unwrap_or_else(stdlib));
+    }
+
+    /// evaluate a file in a frame of its own, only the first time, and define the variables it
+    /// defines in the current frame, with their names prefixed by `prefix`
+    pub fn import(&mut self, name: &str, prefix: &[u8]) -> Val {
+        let err = |msg: String| Error::new(ErrorKind::Import, msg).prim("_ò").into();
+        let dirs = if name.starts_with('/') { vec![String::new()] }
+            else { iter::once(self.dir.c()).chain(self.import_path.iter().cloned()).collect() };
+        let mut found = None;
+        for dir in dirs {
+            let path = if dir.is_empty() { name.to_string() }
+                else { format!("{}/{name}", dir.trim_end_matches('/')) };
+            if self.modules.contains_key(&path) { found = Some((path, None)); break }
+            if let Some(code) = self.interface.read_file(&path) { found = Some((path, Some(code))); break }
+        }
+        let Some((path, code)) = found else { return err(format!("couldn't find {name}")) };
+        let module = match (self.modules.get(&path), code) {
+            (Some(Some(m)), _) => Rc::clone(m),
+            (Some(None), _) => return err(format!("circular import of {path}")),
+            (None, code) => {
+                let Some(code) = code.and_then(|x| String::from_utf8(x).ok()) else {
+                    return err(format!("{path} isn't valid utf-8"))
+                };
+                self.modules.insert(path.c(), None);
+                let dir = path.rsplit_once('/').map_or_else(String::new, |x| x.0.to_string());
+                let dir = core::mem::replace(&mut self.dir, dir);
+                let base = self.stdlib.clone().unwrap_or_else(stdlib);
+                let base = self.stdlib.replace(base);
+                let stack = core::mem::replace(&mut self.stack, vec![Frame::default()]);
+                let value = self.include_string(&code);
+                let vars = core::mem::replace(&mut self.stack, stack).pop().unwrap().vars;
+                self.stdlib = base;
+                self.dir = dir;
+                if value.is_err() { self.modules.remove(&path); return value }
+                let module = Rc::new(Module { value, vars });
+                self.modules.insert(path, Some(Rc::clone(&module)));
+                module
+            },
+        };
+        for (k, v) in &module.vars {
+            let mut name = Bstr::from(prefix);
+            name.extend(k.iter().copied());
+            self.set_local(name, v.c());
+        }
+        module.value.c()
     }
 
     #[cfg(any(feature = "std", test))]
@@ -250,19 +347,21 @@ impl<'io> Env<'io> {
         Ok(self.include_string(&code))
     }
 
-    pub fn run_string(&mut self, code: &str, format: &[Val]) -> Result<(), i32> {
-        self.run_bytes(&crate::codepage::tobytes(code).unwrap()[..], format)
+    pub fn run_string(&mut self, code: &str, format: &[Val]) -> Result<(), Rc<Error>> {
+        self.run_bytes(&tobytes(code)?, format)
     }
 
-    pub fn run_bytes(&mut self, code: &[u8], format: &[Val]) -> Result<(), i32> {
+    /// run a program and write its result to stream 0. if the program fails or calls `⌂`, the
+    /// error is returned instead
+    pub fn run_bytes(&mut self, code: &[u8], format: &[Val]) -> Result<(), Rc<Error>> {
         let mut res = self.include_bytes(code);
-        if let Val::Err(x) = res { return Err(x); }
+        if let Val::Err(e) = res { return Err(e); }
         if res.is_infinite() { res = res.call(
             self,
             self.get_var(&[b!('α')]).unwrap_or(NAN),
             self.get_var(&[b!('β')]),
         ); }
-        if let Val::Err(x) = res { return Err(x); }
+        if let Val::Err(e) = res { return Err(e); }
         res.format(&mut io::FromInterface(&mut *self.interface), format).unwrap();
         Ok(())
     }
@@ -275,4 +374,59 @@ impl<'io> Env<'io> {
         self.set_local(bstr![b!('δ')], Val::lis(args.c()));
     }
 
+}
+
+/// the frame of the standard library. with std, it's only evaluated once per thread
+fn stdlib() -> Rc<Frame> {
+    fn load() -> Rc<Frame> {
+        let mut env = Env::new(bx(rand::rngs::mock::StepRng::new(0, 0)));
+        func::load_intrinsics(&mut env);
+        env.include_string(STDLIB);
+        Rc::new(env.stack.swap_remove(0))
+    }
+    #[cfg(any(feature = "std", test))] {
+        std::thread_local! { static STDLIB_FRAME: Rc<Frame> = load(); }
+        STDLIB_FRAME.with(Rc::clone)
+    }
+    #[cfg(not(any(feature = "std", test)))] load()
+}
+
+// the max_alloc of the env that's running, for list constructors, which don't get one
+#[cfg(any(feature = "std", test))]
+std::thread_local! { static MAX_ALLOC: core::cell::Cell<usize> = const { core::cell::Cell::new(usize::MAX) }; }
+#[cfg(not(any(feature = "std", test)))]
+static MAX_ALLOC: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(usize::MAX);
+
+/// set the limit `check_len` uses, returning the old one
+fn set_max_alloc(n: usize) -> usize {
+    #[cfg(any(feature = "std", test))] { MAX_ALLOC.replace(n) }
+    #[cfg(not(any(feature = "std", test)))] { MAX_ALLOC.swap(n, Ordering::Relaxed) }
+}
+
+/// fail if a list of `len` items is too big for the running env
+fn check_len(len: usize) -> Result<(), Val> {
+    #[cfg(any(feature = "std", test))] let max = MAX_ALLOC.get();
+    #[cfg(not(any(feature = "std", test)))] let max = MAX_ALLOC.load(Ordering::Relaxed);
+    if len > max { return Err(Error::new(ErrorKind::Limit, "list too big").into()) }
+    Ok(())
+}
+
+// point an error at the function application that caused it, unless it already has a location
+fn at(val: Val, span: &Span) -> Val { match val {
+    Val::Err(mut e) if e.span.is_none() => { Rc::make_mut(&mut e).span = Some(*span); Val::Err(e) },
+    x => x,
+}}
+
+fn tobytes(code: &str) -> Result<Vec<u8>, Rc<Error>> {
+    crate::codepage::tobytes(code).ok_or_else(|| {
+        let (mut line, mut col) = (1, 1);
+        for (start, c) in code.chars().enumerate() {
+            if crate::codepage::tobyte(c).is_none() {
+                let span = Span { start, end: start + 1, line, col };
+                return Rc::new(Error::new(ErrorKind::Syntax, format!("{c:?} is not in the codepage")).at(span));
+            }
+            if c == '\n' { line += 1; col = 1; } else { col += 1; }
+        }
+        unreachable!()
+    })
 }
\ No newline at end of file
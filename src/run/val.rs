@@ -1,7 +1,7 @@
 use core::cmp::Ordering;
 
 use super::Val::{self, Num, Int, Lis};
-use super::{NAN, c64, Env, adverb};
+use super::{NAN, c64, Env, TraceFrame, List, adverb, list};
 use crate::prelude::*;
 
 impl Val {
@@ -12,27 +12,44 @@ impl Val {
 
     pub fn is_list(&self) -> bool { matches!(self, Lis {..})}
 
+    pub fn is_err(&self) -> bool { matches!(self, Val::Err(_))}
+
     pub fn is_nan(&self) -> bool { match self { Num(n) => n.is_nan(), _ => false }}
 
-    pub fn is_infinite(&self) -> bool { !matches!(self, Int(_) | Num(_) | Lis {..})}
+    #[cfg(feature="bigint")]
+    pub fn is_big(&self) -> bool { matches!(self, Val::Big(_)) }
+    #[cfg(not(feature="bigint"))]
+    pub fn is_big(&self) -> bool { false }
+
+    /// a `Big` or a `Rat`
+    #[cfg(feature="bigint")]
+    pub fn is_bignum(&self) -> bool { matches!(self, Val::Big(_) | Val::Rat(_)) }
+    #[cfg(not(feature="bigint"))]
+    pub fn is_bignum(&self) -> bool { false }
 
-    pub fn is_scalar(&self) -> bool { matches!(self, Int(_) | Num(_))}
+    pub fn is_infinite(&self) -> bool { !matches!(self, Int(_) | Num(_) | Lis {..} | Val::Dict(_)) && !self.is_bignum() }
+
+    pub fn is_scalar(&self) -> bool { matches!(self, Int(_) | Num(_)) || self.is_bignum() }
 
     pub fn as_bool(&self) -> bool { match self {
         Int(n) => *n != 0,
         Num(n) => !n.is_nan() && *n != c64::new(0., 0.),
+        #[cfg(feature="bigint")] Val::Big(_) | Val::Rat(_) => true,
         _ => false,
     }}
 
     pub fn try_c(&self) -> Option<c64> { match self {
         Int(n) => Some(c64::new(*n as f64, 0.)),
         Num(n) => Some(*n),
+        #[cfg(feature="bigint")] Val::Big(n) => Some(c64::new(super::big::to_f64(n), 0.)),
+        #[cfg(feature="bigint")] Val::Rat(n) => Some(c64::new(super::big::ratio_to_f64(n), 0.)),
         _ => None,
     }}
 
     pub fn try_int(&self) -> Option<i64> { match self {
         Int(n) => Some(*n),
         Num(n) => Some(n.re as i64),
+        #[cfg(feature="bigint")] Val::Rat(n) => Val::big(n.to_integer()).try_int(),
         _ => None
     }}
 
@@ -49,6 +66,9 @@ impl Val {
             || d / b.norm() <= TOLERANCE
         }
         match (self, other) {
+            #[cfg(feature="bigint")]
+            (Val::Big(_) | Val::Rat(_), _) | (_, Val::Big(_) | Val::Rat(_)) => self == other || self.is_scalar() && other.is_scalar()
+                && close(self.as_c(), other.as_c()),
             (Num(l), Num(r)) => close(*l, *r) || l.is_nan() && r.is_nan(),
             (Int(l), Int(r)) => l == r,
             (Num(l), Int(r)) => close(*l, c64::new(*r as f64, 0.)),
@@ -57,22 +77,38 @@ impl Val {
                 l_fill == r_fill
                 && l_l.len() == r_l.len()
                 && l_l.iter().zip(r_l.iter()).all(|(x, y)| Val::approx(x, y)),
+            (Val::Dict(l), Val::Dict(r)) =>
+                l.keys() == r.keys() && l.values().iter().zip(r.values()).all(|(x, y)| x.approx(y)),
             _ => false
         }
     }
 
+    /// total order of values: numbers, then lists, then dicts, then functions. lists are compared
+    /// by their items, then by length, then by fill, and dicts by their entries in order.
+    /// functions only compare equal to themselves
     pub fn cmpval(&self, other: &Val) -> Ordering {
         match (self, other) {
             (Int(m), Int(n)) => m.cmp(n),
+            (Lis { l: a, fill: af }, Lis { l: b, fill: bf }) => match (&**a, &**b) {
+                (List::Ints(a, _), List::Ints(b, _)) => a.cmp(b),
+                (a, b) => a.items().zip(b.items()).map(|(x, y)| x.cmpval(&y)).find(|x| x.is_ne())
+                    .unwrap_or_else(|| a.len().cmp(&b.len())),
+            }.then_with(|| af.cmpval(bf)),
+            #[cfg(feature="bigint")]
+            (Val::Big(_) | Val::Rat(_), _) | (_, Val::Big(_) | Val::Rat(_)) if self.is_scalar() && other.is_scalar() =>
+                super::big::cmp(self, other),
+            (Val::Dict(a), Val::Dict(b)) => a.iter().zip(b.iter())
+                .map(|((ak, av), (bk, bv))| ak.cmpval(bk).then_with(|| av.cmpval(bv))).find(|x| x.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
             (a, b) => match (a.try_c(), b.try_c()) {
                 (Some(m), Some(n)) => complexcmp(m, n),
                 (None,    Some(_)) => Ordering::Greater,
                 (Some(_), None) => Ordering::Less,
-                (None,    None) => Ordering::Less,
+                (None,    None) => cmpfunc(a, b),
             }
         }
     }
-        
+
     pub fn monad(&self, env: &mut Env, a: Val) -> Val { 
         self.call(env, a, None)
     }
@@ -82,30 +118,57 @@ impl Val {
     }
 
     pub fn call(&self, env: &mut Env, a: Val, b: Option<Val>) -> Val {
+        ok!(env.tick());
+        env.deeper(|env| self.traced(env, a, b))
+    }
+
+    fn traced(&self, env: &mut Env, a: Val, b: Option<Val>) -> Val {
+        if !env.trace || !matches!(self, Val::Dfn {..} | Val::Fork(..) | Val::Av(..) | Val::Memo(_)) {
+            return self.apply(env, a, b)
+        }
+        // the arguments are given away, so only their shapes are kept for the frame
+        let shapes = iter::once(&a).chain(&b).map(|x| list::ishape(x, i64::MAX)).collect();
+        match self.apply(env, a, b) {
+            Val::Err(mut e) => {
+                let span = match self {
+                    Val::Dfn { span, .. } => Some(*span),
+                    Val::Memo(m) => if let Val::Dfn { span, .. } = &m.f { Some(*span) } else { None },
+                    _ => None,
+                };
+                Rc::make_mut(&mut e).trace.push(TraceFrame { func: self.describe(), shapes, span });
+                Val::Err(e)
+            },
+            x => x,
+        }
+    }
+
+    fn apply(&self, env: &mut Env, a: Val, b: Option<Val>) -> Val {
         match self {
-            Val::Err(x) => Val::Err(*x),
-            Lis { .. } | Num(_) | Int(_) => self.c(),
+            Val::Err(_) => self.c(),
+            Lis { .. } | Val::Dict(_) | Num(_) | Int(_) => self.c(),
+            #[cfg(feature="bigint")] Val::Big(_) | Val::Rat(_) => self.c(),
             Val::FSet(name) => {
                 env.set_local(name.c(), a.c());
                 b.unwrap_or(a)
             },
+            Val::FPat(pat) => {
+                ok!(super::code::destructure(env, pat, a.c()));
+                b.unwrap_or(a)
+            },
             Val::FCng(name) => env.mutate_var(name, a, b).unwrap_or(NAN),
-            Val::Dfn { s, loc } => {
-                env.stack.push((**loc).c());
-                env.set_local(bstr![b!('Σ')], Int(1 + i64::from(b.is_some())));
-                env.set_local(bstr![b!('α')], a);
-                env.set_local(bstr![b!('β')], b.unwrap_or(NAN));
-                env.set_local(bstr![b!('ƒ')], self.c());
-                let val = env.eval_block(s);
+            Val::Dfn { code, loc, .. } => {
+                env.stack.push(super::code::dfn_frame(self, loc, a, b));
+                let val = env.exec(code);
                 env.stack.pop();
                 val
             },
             Val::Fork(aa, ff, bb) => {
-                let l = aa.call(env, a.c(), b.c());
-                let r = bb.call(env, a, b);
+                let l = ok!(aa.call(env, a.c(), b.c()));
+                let r = ok!(bb.call(env, a, b));
                 ff.dyad(env, l, r)
             }
             Val::AvBuilder(t) => Val::Av(*t, b.map(|x| x.rc()), a.rc()),
+            Val::Memo(m) => m.call(self, env, a, b),
             Val::Av(t, f, g) => t(env, a, b, f.as_ref(), g),
             Val::Func(f) => f(env, a, b),
         }
@@ -125,16 +188,52 @@ impl PartialEq for Val {
             (Int(l), Int(r)) => l == r,
             (Num(l), Int(r)) => l.im == 0. && l.re == *r as f64,
             (Int(l), Num(r)) => r.im == 0. && r.re == *l as f64,
+            #[cfg(feature="bigint")]
+            (Val::Big(_) | Val::Rat(_), _) | (_, Val::Big(_) | Val::Rat(_)) =>
+                self.is_scalar() && other.is_scalar() && super::big::cmp(self, other).is_eq(),
             (Lis { l: l_l, fill: l_fill }, Lis { l: r_l, fill: r_fill }) => 
-                l_fill == r_fill
-                && l_l.len() == r_l.len()
-                && l_l.iter().zip(r_l.iter()).all(|(x, y)| x == y),
-            _ => false
+                l_fill == r_fill && l_l == r_l,
+            (Val::Dict(l), Val::Dict(r)) => l == r,
+            (Lis { .. } | Val::Dict(_) | Num(_) | Int(_), _) | (_, Lis { .. } | Val::Dict(_) | Num(_) | Int(_)) => false,
+            (a, b) => cmpfunc(a, b).is_eq(),
         }
     }
 }
 impl Eq for Val {}
 
+/// order of non-data values, by kind and then by structure. primitives and adverbs are ordered by
+/// name, and everything else that isn't structural by where it was defined, then by address
+fn cmpfunc(a: &Val, b: &Val) -> Ordering {
+    use super::func::intrinsic_name;
+    let kind = |x: &Val| match x {
+        #[cfg(feature="bigint")] Val::Big(_) | Val::Rat(_) => 0,
+        Num(_) | Int(_) => 0, Lis { .. } => 1, Val::Dict(_) => 2, Val::FSet(_) => 3, Val::FPat(_) => 4, Val::FCng(_) => 5,
+        Val::Func(_) => 6, Val::AvBuilder(_) => 7, Val::Av(..) => 8, Val::Fork(..) => 9, Val::Dfn { .. } => 10,
+        Val::Memo(_) => 11, Val::Err(_) => 12,
+    };
+    let prim = |x: usize| (intrinsic_name(x), x);
+    match (a, b) {
+        (Val::FSet(a), Val::FSet(b)) | (Val::FCng(a), Val::FCng(b)) => a.cmp(b),
+        (Val::FPat(a), Val::FPat(b)) => a.cmp(b),
+        (Val::Func(a), Val::Func(b)) => prim(*a as usize).cmp(&prim(*b as usize)),
+        (Val::AvBuilder(a), Val::AvBuilder(b)) => prim(*a as usize).cmp(&prim(*b as usize)),
+        (Val::Av(at, af, ag), Val::Av(bt, bf, bg)) => prim(*at as usize).cmp(&prim(*bt as usize))
+            .then_with(|| match (af, bf) {
+                (Some(a), Some(b)) => a.cmpval(b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            })
+            .then_with(|| ag.cmpval(bg)),
+        (Val::Fork(aa, af, ab), Val::Fork(ba, bf, bb)) =>
+            aa.cmpval(ba).then_with(|| af.cmpval(bf)).then_with(|| ab.cmpval(bb)),
+        (Val::Dfn { loc: al, code: ac, span: asp }, Val::Dfn { loc: bl, code: bc, span: bsp }) =>
+            (asp.start, asp.end, Rc::as_ptr(ac), Rc::as_ptr(al))
+            .cmp(&(bsp.start, bsp.end, Rc::as_ptr(bc), Rc::as_ptr(bl))),
+        (Val::Memo(a), Val::Memo(b)) => Rc::as_ptr(a).cmp(&Rc::as_ptr(b)),
+        (Val::Err(a), Val::Err(b)) => Rc::as_ptr(a).cmp(&Rc::as_ptr(b)),
+        (a, b) => kind(a).cmp(&kind(b)),
+    }
+}
+
 pub fn complexcmp(a: c64, b: c64) -> Ordering {
     fn good_cmp(a: f64, b: f64) -> Ordering {
         if a == 0. && b == 0. { return Ordering::Equal; } // make -0.0 and 0.0 compare equal
@@ -154,6 +253,7 @@ func!(a :encode b => {
     match a {
         Int(n) => encode_int(n, b),
         Num(n) => encode_flt(n.re, b),
+        #[cfg(feature="bigint")] Val::Big(n) => encode_big((*n).c(), b),
         _ => NAN,
     }
 });
@@ -171,6 +271,22 @@ fn encode_int(mut a: i64, b: Val) -> Val {
     Val::lis(list)
 }
 
+#[cfg(feature="bigint")]
+fn encode_big(mut a: num_bigint::BigInt, b: Val) -> Val {
+    use num_traits::Zero;
+    let mut list = vec![Int(0); b.len() + 1];
+    for (n, i) in b.into_iterf().enumerate().rev() {
+        let i = or_nan!(super::big::int(&i));
+        if i.is_zero() { list[n+1] = Val::big(a); return Val::lis(list); }
+        let m = &a % &i;
+        a = super::big::div_euclid(&a, &i);
+        list[n+1] = Val::big(m);
+        if a.is_zero() { return Val::lis(list); }
+    }
+    list[0] = Val::big(a);
+    Val::lis(list)
+}
+
 fn encode_flt(mut a: f64, b: Val) -> Val {
     let mut list = vec![Val::flt(0.); b.len() + 1];
     for (n, i) in b.into_iterf().enumerate().rev() {
@@ -186,6 +302,18 @@ fn encode_flt(mut a: f64, b: Val) -> Val {
 
 impl core::hash::Hash for Val {
 fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+    // whole numbers are equal to ints, so they have to hash the same
+    if let Num(n) = self { if n.im == 0. && n.re.fract() == 0. && n.re.abs() < i64::MAX as f64 {
+        return Int(n.re as i64).hash(state)
+    }}
+    // and the other whole numbers to bigints
+    #[cfg(feature="bigint")]
+    if let Num(n) = self { if let Some(n) = super::big::whole(*n) { return Val::big(n).hash(state) }}
+    // and fractions to the floats with their value
+    #[cfg(feature="bigint")]
+    if let Val::Rat(n) = self { let f = super::big::ratio_to_f64(n);
+        if super::big::ratio(&Val::flt(f)).as_ref() == Some(n) { return Val::flt(f).hash(state) }
+    }
     core::mem::discriminant(self).hash(state);
     match self {
         Num(mut n) => {
@@ -195,14 +323,19 @@ fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
             state.write(&normalize(n.im).to_ne_bytes());
         },
         Int(n) => state.write_i64(*n),
+        #[cfg(feature="bigint")] Val::Big(n) => n.hash(state),
+        #[cfg(feature="bigint")] Val::Rat(n) => n.hash(state),
         Lis { l, fill } => (fill, l).hash(state),
+        Val::Dict(d) => d.hash(state),
         Val::FSet(n) | Val::FCng(n) => n.hash(state),
+        Val::FPat(p) => p.hash(state),
         Val::Fork(a, f, b) => (a, f, b).hash(state),
         Val::Av(t, f, g) => (*t as usize, f, g).hash(state),
         Val::AvBuilder(t) => (*t as usize).hash(state),
-        Val::Err(x) => x.hash(state),
+        Val::Err(e) => (e.kind, e.code).hash(state),
         // these are hashed by reference
-        Val::Dfn { loc, s } => (Rc::as_ptr(loc), s.as_ptr()).hash(state),
+        Val::Dfn { loc, code, .. } => (Rc::as_ptr(loc), Rc::as_ptr(code)).hash(state),
+        Val::Memo(m) => Rc::as_ptr(m).hash(state),
         Val::Func(x) => (*x as usize).hash(state),
     }
 }
@@ -6,15 +6,28 @@ use crate::{prelude::*, run::io::io_result};
 
 const DOCS: &str = include_str!("../doc/raw.txt");
 
+fn env<'io>() -> crate::Env<'io> {
+    let mut env = crate::Env::new(bx(rngs::mock::StepRng::new(0, 0)));
+    env.include_stdlib();
+    env
+}
+
 #[test]
 fn docs() -> Result<(), String> {
     println!("doing doc tests...");
     let mut tested = 0;
     let mut failed = 0;
-    let mut env = super::run::Env::new(bx(rngs::mock::StepRng::new(0, 0)));
-    env.include_stdlib();
+    let mut env = env();
+    // `:needs: feature` skips the next example when that feature is off
+    let mut skip = false;
     for (n, line) in DOCS.lines().enumerate() {
-        if let Some(line) = line.strip_prefix("> ") {
+        if let Some(feature) = line.strip_prefix(":needs: ") {
+            skip = !match feature {
+                "bigint" => cfg!(feature="bigint"),
+                _ => return Err(format!("doc/raw.txt:{}: unknown feature {feature}", n+1)),
+            };
+        } else if let Some(line) = line.strip_prefix("> ") {
+            if core::mem::take(&mut skip) { continue }
             if let Some((i, o)) = line.split_once(" ≡ ") {
                 let left = env.include_string(i);
                 let right = env.include_string(o);
@@ -101,9 +114,8 @@ fn input_output() -> Result<(), ()> {
         Vec::new(),
         Vec::new()));
 
-    let mut env = crate::Env::new(bx(rngs::mock::StepRng::new(0, 0)));
+    let mut env = env();
     env.interface = bx(TestIO(&refcell));
-    env.include_stdlib();
     env.include_string(PROGRAM);
 
     let (_, _, o, e) = refcell.take();
@@ -122,6 +134,348 @@ fn input_output() -> Result<(), ()> {
     }
 }
 
+#[test]
+fn errors() {
+    use crate::ErrorKind;
+    let refcell = RefCell::default();
+    let mut env = env();
+    env.interface = bx(TestIO(&refcell));
+    let e = env.run_string("1+{3⌂}2", &[]).unwrap_err();
+    assert_eq!((e.kind, e.code, e.prim), (ErrorKind::Exit, 3, Some("⌂")));
+    let e = env.run_string("\"bye\"⌂·4", &[]).unwrap_err();
+    assert_eq!((e.kind, e.code, &e.message[..]), (ErrorKind::Exit, 1, "bye"));
+    assert!(env.run_string("4+4", &[]).is_ok());
+    assert_eq!(&refcell.take().2, b"bye\n8");
+    env.trace = true;
+    let e = env.run_string("(1 2){3⌂}2", &[]).unwrap_err();
+    assert_eq!(e.trace.len(), 1);
+    assert_eq!((&e.trace[0].shapes[..], e.trace[0].span.map(|x| x.col)), (&[vec![2], vec![]][..], Some(6)));
+    // a memoized dfn runs inside of the memo, so the memo is what shows up
+    let e = env.run_string("2_╤{3⌂}", &[]).unwrap_err();
+    assert_eq!(e.trace.len(), 1);
+    assert!(e.trace[0].func.starts_with("[inmemo"), "{}", e.trace[0].func);
+    assert_eq!(e.trace[0].span.map(|x| x.col), Some(4));
+    let e = env.run_string("(1 2)⌂5", &[]).unwrap_err();
+    assert_eq!((e.kind, e.code, e.payload.as_ref().map(|x| x.len())), (ErrorKind::User, 5, Some(2)));
+    assert!(env.run_string("{0}╝{α⌂2}", &[]).is_ok());
+    assert!(env.run_string("{0}╝{3⌂}", &[]).is_err());
+    for (code, kind, prim) in [
+        ("(1 2)_↑(3 4)", ErrorKind::Type, "_↑"), ("1_,(⎕(1 2))", ErrorKind::Type, "_,"),
+        ("123↑(.+)", ErrorKind::Type, "↑"), ("(.+)♂1", ErrorKind::Type, "♂"), ("5£1", ErrorKind::Type, "£"),
+        (":300ê", ErrorKind::Domain, "ê"), (":999ë", ErrorKind::Domain, "ë"), ("123@(3/2)", ErrorKind::Index, "@"),
+    ] {
+        let e = env.run_string(code, &[]).expect_err(code);
+        assert_eq!((e.kind, e.prim), (kind, Some(prim)), "{code}");
+    }
+    // missing values are still the fill
+    assert!(env.run_string("(123@5)+(0/0)", &[]).is_ok());
+}
+
+#[test]
+fn adverb_errors() {
+    use crate::ErrorKind;
+    let mut env = env();
+    for code in [
+        "123╕E", "123E╕4", "123╒E", "123╛E", "123┼E", "123_╛E", // each
+        "123╤E", "123╧E", "123╪E", "123 2╫E", "123 E╫+", // scan & reduce
+        "1E║+2", "1+║E 2", "1E╜+2", "1E╙+2", "1E╖+2", "1E╓+2", "1E _╜+", "1+_╜E", // composition
+        "1 2┴E", "1┐E", "123E╗▲", "123 1╗E", "123 1╔E", "123E╔▲", "123E_╔▲", "123♠_╔E", "123_┐E", "123_╒E", "123_┌E 1", "123_╕E", // misc
+        "1E╩▲", "1E╦▲", "1E _╩▲", "1E _╦▲", "1 3╨E", "1 E╨▲", "1 3╥E", // loops
+        "(1 2)E↔·1", "(1 2 3)·E¿", "123↑(1E)", // primitives
+        "E→f· .f→(ab)", "E→f· .f─(ab)1", // destructuring
+    ] {
+        let code = code.replace('E', "{9⌂7}");
+        let e = env.run_string(&code, &[]).expect_err(&code);
+        assert_eq!((e.kind, e.code), (ErrorKind::User, 7), "{code}");
+    }
+}
+
+#[test]
+fn fuel() {
+    use crate::ErrorKind;
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    let mut env = env();
+    env.fuel = Some(10000);
+    let e = env.run_string("1{0}╩▲", &[]).unwrap_err();
+    assert_eq!((e.kind, env.fuel), (ErrorKind::Fuel, Some(0)));
+    env.fuel = Some(10000);
+    assert_eq!(env.run_string("{0}╝(1{0}╩▲)", &[]).unwrap_err().kind, ErrorKind::Fuel);
+    env.fuel = None;
+    let cancel = Arc::new(AtomicBool::new(true));
+    env.cancel = Some(Arc::clone(&cancel));
+    assert_eq!(env.run_string("1{0}╩▲", &[]).unwrap_err().kind, ErrorKind::Cancelled);
+    cancel.store(false, Ordering::Relaxed);
+    assert!(matches!(env.include_string("2+2"), crate::Val::Int(4)));
+}
+
+#[test]
+fn limits() {
+    use crate::ErrorKind;
+    let mut env = env();
+    env.max_depth = 50;
+    assert!(!env.include_string(":40{=0?0◘(α▼ƒ)+1}").is_err());
+    let e = env.run_string(":60{=0?0◘(α▼ƒ)+1}", &[]).unwrap_err();
+    assert_eq!((e.kind, &e.message[..]), (ErrorKind::Limit, "too much recursion"));
+    assert_eq!(env.stack.len(), 1);
+    // memos and adverbs count too, and so does going into nested lists
+    let nested = format!("({}1)+1", "♪".repeat(60));
+    for code in [":60_╤{α=0?0◘(α▼)ƒ+1}", ":60{α=0?0◘(α▼)╕ƒ}", &nested] {
+        let e = env.run_string(code, &[]).expect_err(code);
+        assert_eq!((e.kind, &e.message[..]), (ErrorKind::Limit, "too much recursion"), "{code}");
+        assert_eq!(env.depth, 0);
+    }
+    env.max_alloc = 1000;
+    for code in [":1001↕", "(:100:100)↕", "1↑(:100:100)", "123§:400", "123‼:400", "123‼(1 1 :999)", "(:600↕),(:600↕)"] {
+        let e = env.run_string(code, &[]).expect_err(code);
+        assert_eq!((e.kind, &e.message[..]), (ErrorKind::Limit, "list too big"), "{code}");
+    }
+    assert!(!env.include_string(":1000↕").is_err());
+}
+
+// the default depth is reached before the stack runs out. debug builds need a bigger one
+#[test]
+fn default_depth() {
+    let stack = if cfg!(debug_assertions) { 1 << 30 } else { 64 << 20 };
+    std::thread::Builder::new().stack_size(stack).spawn(|| {
+        let mut env = env();
+        for (code, res) in [
+            (":149{=0?0◘(α▼ƒ)+1}", "149"),
+            ("(:300↕){α≡φ?0◘(α♂)ƒ+1}", "300"),
+            ("(:1000_╤{α<2?α◘(α▼)ƒ+((α-2)ƒ)})>0", "1"),
+        ] {
+            let val = env.include_string(code);
+            assert_eq!(val.display_string(), res, "{code}");
+        }
+        // counting to 0 from n makes n+1 nested calls (twice as many when an adverb makes them),
+        // and the comparison with 0 nests a few more
+        for (code, per) in [("{=0?0◘(α▼ƒ)+1}", 1), ("{α=0?0◘(α▼)╕ƒ}", 2), ("_╤{α=0?0◘(α▼)ƒ+1}", 1)] {
+            let n = env.max_depth / per;
+            assert!(!env.include_string(&format!(":{}{code}", n - 10)).is_err(), "{code}");
+            let e = env.run_string(&format!(":{n}{code}"), &[]).expect_err(code);
+            assert_eq!(e.kind, crate::ErrorKind::Limit, "{code}");
+        }
+    }).unwrap().join().unwrap();
+}
+
+#[test]
+fn tail_calls() {
+    use crate::ErrorKind;
+    let mut env = env();
+    env.max_depth = 50;
+    // the frames are reused, so they all go back to the top level
+    for code in [":1000{=0?0◘α▼ƒ}", ":1000{α=0?β◘(α▼)ƒ(β+α)}0", ":1000{α=0?β◘[(α▼)ƒ(β+α)]}0"] {
+        assert!(!env.include_string(code).is_err(), "{code}");
+        assert_eq!(env.stack.len(), 1);
+    }
+    let e = env.run_string(":60{=0?0◘(α▼ƒ)+1}", &[]).unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Limit);
+}
+
+#[test]
+fn packed_lists() {
+    use crate::{Val, run::List};
+    use core::hash::BuildHasher;
+    let mut env = env();
+    let hasher = HashMap::<(), ()>::new().hasher().clone();
+    let hash = |x: &Val| hasher.hash_one(x);
+    for code in [
+        "I*2", "I+0.5", "I-I", "1-I", "IÑ2", "I<2", "I=I♠", "I╤+", "I╧Ñ", "I╧+", "I╧*", "I-", "I≤", "I≥", "I+I↑3",
+    ] {
+        let packed = env.include_string(&code.replace('I', "(:5↕)"));
+        let unpacked = env.include_string(&code.replace('I', "((:5↕)╕{α})"));
+        assert!(packed == unpacked && hash(&packed) == hash(&unpacked), "{code}");
+    }
+    let Val::Lis{l, ..} = env.include_string("(:5↕)*2") else { panic!("not a list") };
+    assert!(matches!(*l, List::Ints(..)));
+}
+
+#[test]
+fn destructuring() {
+    use crate::{ErrorKind, Val};
+    let mut env = env();
+    // the function form displays like its pattern
+    assert_eq!(format!("{}", env.include_string("─(a .bc(■ ,d))")), "→(a .bc (■ ,d))");
+    for (code, col, msg) in [
+        ("(1 2)→(ab", 6, "unclosed pattern"), ("(1 2)─(a(b", 9, "unclosed pattern"),
+        ("(1 2 3)→(a,b,c)", 13, "more than one rest in a pattern"), ("(1 2)→(a 1)", 10, "unexpected token in pattern"),
+        // errors come out of dfns and conditionals too
+        ("{(1 2)→(a 1)}", 11, "unexpected token in pattern"), ("1?(1 2)→(ab", 8, "unclosed pattern"),
+    ] {
+        let Val::Err(e) = env.include_string(code) else { panic!("{code}") };
+        assert_eq!((e.kind, e.span.map(|s| s.col)), (ErrorKind::Syntax, Some(col)), "{code}");
+        assert!(e.message.starts_with(msg), "{code}");
+    }
+}
+
+#[test]
+fn under() {
+    use crate::ErrorKind;
+    let mut env = env();
+    for (code, msg) in [
+        ("123(+1)_╔▲", "selector isn't structural"), ("123(╕▲)_╔▲", "selector isn't structural"),
+        ("123(↑2)_╔\"abc\"", "modified selection changed shape"), ("(12)(34)(@0)_╔♦", "modified selection changed shape"),
+        ("0123(¿00)_╔(+Φ)", "selector uses an item twice"), ("123(‼2)_╔(+(012345))", "selector uses an item twice"),
+        ("1526{α‼(α>3)}_╔(+Φ)", "selector isn't structural"),
+    ] {
+        let e = env.run_string(code, &[]).expect_err(code);
+        assert_eq!((e.kind, &e.message[..], e.prim), (ErrorKind::Inverse, msg, Some("_╔")), "{code}");
+    }
+}
+
+#[test]
+fn inverses() {
+    use crate::ErrorKind;
+    let mut env = env();
+    for code in ["5_┴+", "5_┴{α+1}", "5(_1)╨(1╜◄)", "\"abc\"_┴Ç"] {
+        let e = env.run_string(code, &[]).expect_err(code);
+        assert_eq!((e.kind, &e.message[..]), (ErrorKind::Inverse, "function has no inverse"), "{code}");
+    }
+}
+
+#[test]
+fn memo() {
+    use crate::Val::Int;
+    let mut env = env();
+    env.include_string("{▲↔n· α<2?α◘(α-1)ƒ+((α-2)ƒ)}→fib· _╤.fib→mfib· 3_╥.fib→bfib· 0_╥.fib→nfib");
+    // how many times the function was called
+    let calls = |env: &mut crate::Env, code: &str| {
+        env.include_string("0→n");
+        env.include_string(code);
+        env.include_string("n")
+    };
+    // recursive calls hit the cache too
+    assert!(matches!(calls(&mut env, ":80:mfib"), Int(81)));
+    assert!(matches!(calls(&mut env, ":80:mfib"), Int(0)));
+    assert!(matches!(calls(&mut env, ":90:bfib"), Int(91)));
+    assert!(matches!(calls(&mut env, ":20:nfib"), Int(21891)));
+    // memoizing again starts with an empty cache
+    env.include_string("_╤.mfib→mfib");
+    assert!(matches!(calls(&mut env, ":80:mfib"), Int(81)));
+    // forgetting empties the memo itself, so everything that refers to it forgets too
+    env.include_string(".mfib→same· _╧.mfib");
+    assert!(matches!(calls(&mut env, ":80:same"), Int(81)));
+    assert!(matches!(calls(&mut env, ":80:mfib"), Int(0)));
+    // errors aren't remembered
+    env.include_string("0→n· _╤{▲↔n· 9⌂7}→err");
+    for _ in 0..2 { assert!(env.run_string("1:err", &[]).is_err()); }
+    assert!(matches!(env.include_string("n"), Int(2)));
+    env.include_string("_╤{▲↔n· α+β}→add");
+    assert!(matches!(calls(&mut env, "1:add 2· 1:add 2· 2:add 1"), Int(2)));
+}
+
+#[test]
+fn sort_by() {
+    let mut env = env();
+    // keys are computed once for each item
+    env.include_string("0→n· 987654321_┐{▲↔n· α}");
+    assert!(matches!(env.include_string("n"), crate::Val::Int(9)));
+}
+
+#[test]
+fn stdlib_shared() {
+    let mut a = env();
+    a.include_string("*2↔inadd ↔incatch");
+    assert!(!matches!(a.get_var(b"inadd"), Some(crate::Val::Func(_))));
+    assert!(a.get_var(b"incatch").is_none());
+    let b = env();
+    assert!(matches!(b.get_var(b"inadd"), Some(crate::Val::Func(_))));
+    assert!(b.get_var(b"incatch").is_some());
+    // envs see the same frame instead of copies of it
+    assert!(Rc::ptr_eq(b.stdlib.as_ref().unwrap(), env().stdlib.as_ref().unwrap()));
+}
+
+#[test]
+fn ordering() {
+    use core::hash::BuildHasher;
+    let mut env = env();
+    let hasher = HashMap::<(), ()>::new().hasher().clone();
+    let vals = vec![
+        "3", "4/2", "2", "■", "(1 2)", "(1 2▐5)", "(1 2 0)", "(2)↕", "\"ab\"", "((1 2) 3)", "φ",
+        ".+", ".-", ".inadd", "+╕", "+╕2", "└+/~", "{1}", "→x", "↔x", "∞", "∞-",
+        "⎕()", "⎕(1 2)", "⎕(1 4/2)", "⎕(1 2 3 4)", "⎕(3 4 1 2)", "⎕(\"ab\" 1)",
+    ];
+    #[cfg(feature="bigint")]
+    let vals = [vals, vec!["2^:64", "2^:64.0", "(2^:64)-", ":9223372036854775807+1", "2^:63.0", "2^:99"]].concat();
+    let vals = vals.into_iter().map(|x| env.include_string(x)).collect::<Vec<_>>();
+    for a in &vals { for b in &vals {
+        let ord = a.cmpval(b);
+        assert_eq!(ord.is_eq(), a == b, "{a} {b}");
+        assert_eq!(ord.reverse(), b.cmpval(a), "{a} {b}");
+        if a == b { assert_eq!(hasher.hash_one(a), hasher.hash_one(b), "{a} {b}"); }
+    }}
+}
+
+#[cfg(feature="bigint")]
+#[test]
+fn bigints() {
+    let mut env = env();
+    env.max_alloc = 1000;
+    let e = env.run_string("2^:1001", &[]).unwrap_err();
+    assert_eq!((e.kind, &e.message[..]), (crate::ErrorKind::Limit, "number too big"));
+}
+
+#[test]
+fn dicts() {
+    let mut env = env();
+    // dicts display like their literal
+    for x in ["⎕()", "⎕(1 2 3 4)", "⎕((1 2) (3 4) ■ ⎕(1 2))"] {
+        let d = env.include_string(x);
+        assert!(env.include_string(&format!("{d}")) == d, "{x}");
+    }
+}
+
+struct FilesIO<'a>(HashMap<&'a str, &'a str>, &'a RefCell<usize>);
+impl crate::Interface<'_> for FilesIO<'_> {
+    fn read(&mut self, _: usize, _: &mut [u8]) -> Option<usize> { None }
+    fn write(&mut self, _: usize, _: &[u8]) -> Option<usize> { None }
+    fn read_file(&mut self, path: &str) -> Option<Vec<u8>> {
+        let file = self.0.get(path)?;
+        *self.1.borrow_mut() += 1;
+        Some(file.as_bytes().to_vec())
+    }
+}
+
+#[test]
+fn imports() {
+    use crate::ErrorKind;
+    let reads = RefCell::new(0);
+    let files = HashMap::from([
+        ("main/a.vemf", "{α*2}→double :10→ten \"lib/b.vemf\"_ò· 5"),
+        ("main/lib/b.vemf", "3→three"),
+        ("path/c.vemf", "7→seven"),
+        ("main/loop.vemf", "\"loop.vemf\"_ò"),
+    ]);
+    let mut env = env();
+    env.interface = bx(FilesIO(files, &reads));
+    env.dir = String::from("main");
+    env.import_path = vec![String::from("path")];
+    let val = env.include_string("\"a.vemf\"_ò→v \"a.vemf\"_ò\"m\"· \"c.vemf\"_ò· .v,(.ten:double),.three,.mten,.seven");
+    assert!(val == [5, 20, 3, 10, 7].into_iter().map(crate::Val::Int).collect(), "{val}");
+    assert_eq!(*reads.borrow(), 3);
+    for (code, msg) in [("\"d.vemf\"_ò", "couldn't find d.vemf"), ("\"loop.vemf\"_ò", "circular import of main/loop.vemf")] {
+        let e = env.run_string(code, &[]).unwrap_err();
+        assert_eq!((e.kind, &e.message[..]), (ErrorKind::Import, msg));
+    }
+}
+
+#[test]
+fn syntax_errors() {
+    use crate::{ErrorKind, Val};
+    let mut env = env();
+    let Val::Err(e) = env.include_string("4→x\n{α+1)2}") else { panic!("no error") };
+    assert_eq!((e.kind, e.span.map(|s| (s.line, s.col))), (ErrorKind::Syntax, Some((2, 5))));
+    assert!(e.message.ends_with("{α+1)2}\n    ^"));
+    let Val::Err(e) = env.include_string("1+{3⌂}2") else { panic!("no error") };
+    assert_eq!(e.span.map(|s| (s.line, s.col)), Some((1, 5)));
+    // statements that call a function
+    for (code, col) in [("3→n· (⌂4)↔n", 6), ("1· ⌂?3", 4), ("1?⌂?3◘4", 3)] {
+        let Val::Err(e) = env.include_string(code) else { panic!("{code}") };
+        assert_eq!(e.span.map(|s| (s.line, s.col)), Some((1, col)), "{code}");
+    }
+    assert!(matches!(env.include_string("2+日"), Val::Err(e) if e.kind == ErrorKind::Syntax));
+}
+
 fn dispbytes(a: &[u8]) -> String { a.iter().map(|x| match x {
     b'\\' => r"\\".to_string(), b'\"' => "\\\"".to_string(), b'\n' => "\\n".to_string(),
     b'\0'..=b'\x1F' | b'\x7F'..=b'\xFF' => format!("\\x{x:02x}"),
@@ -130,8 +484,7 @@ fn dispbytes(a: &[u8]) -> String { a.iter().map(|x| match x {
 
 #[test]
 fn scripts() {
-    let mut env = crate::Env::new(bx(rngs::mock::StepRng::new(0, 0)));
-    env.include_stdlib();
+    let mut env = env();
     assert_eq!(&env.include_string(include_str!("../scripts/pascal.vemf")).display_string(), &r#"
                                                1                                          
                                             1     1                                       
@@ -229,8 +582,7 @@ macro_rules! aoc { ($name:ident,$num:tt, $res:expr) => {
         let refcell = RefCell::new((
             VecDeque::from(*include_bytes!(concat!("../scripts/aoc2022/in/d",$num,".txt"))),
             VecDeque::<u8>::new(), Vec::<u8>::new(), Vec::<u8>::new()));
-        let mut env = crate::Env::new(bx(rngs::mock::StepRng::new(0, 0)));
-        env.include_stdlib();
+        let mut env = env();
         env.interface = bx(TestIO(&refcell));
         env.run_string(
             include_str!(concat!("../scripts/aoc2022/d",$num,".vemf")),
@@ -6,10 +6,24 @@ pub enum Tok {
     Just(u8), White(u8), Comment(Bstr),
     VNoun(Bstr), VVerb(Bstr), VAv1(Bstr), VAv2(Vec<Bstr>, Bstr),
     VSet(Bstr), VMut(Bstr), VSetS(Bstr), VMutS(Bstr),
+    /// `─(` and `→(`, followed by a pattern
+    PSet, PSetS,
     Chr(u8), Chr2(u8, u8), Num(i64), Flt(c64), Str(Bstr)
 }
 use Tok::*;
 
+/// where a token is in the source code. `start` and `end` are byte offsets into the codepage
+/// bytes, `line` and `col` are 1-based and point at `start`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span { pub start: usize, pub end: usize, pub line: usize, pub col: usize }
+
+impl Span {
+    /// span covering from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span { Span { end: other.end, ..self } }
+}
+
+pub type Spanned = (Tok, Span);
+
 #[inline]
 fn step(t: &mut&[u8]) -> Option<u8> {
     let a = t.first().copied();
@@ -121,8 +135,9 @@ fn token(first: Option<u8>, t: &mut &[u8]) -> Option<Tok> {
     Some(match first {
         Some(b'"') => Str(string(t)),
         Some(b!('█')) => {
-            let Some(n @ b'1'..=b'8') = step(t) else { return None }; // rethink this
-            byte_lit(t, n - b'0')
+            // the parser will complain about the lone █
+            let Some(n @ b'1'..=b'8') = t.first().copied() else { return Some(Just(b!('█'))) };
+            step(t); byte_lit(t, n - b'0')
         }
         Some(b'`') => Chr(step(t).unwrap_or(0x20)),
         Some(b'_') => {
@@ -153,6 +168,8 @@ fn token(first: Option<u8>, t: &mut &[u8]) -> Option<Tok> {
         },
         Some(b!('.')) => VNoun(ident(t)), Some(b!('¨')) => Str  (ident(t)),
         Some(b!('•')) => VAv1 (ident(t)), Some(b!('○')) => VAv2 (vec![], ident(t)),
+        Some(b!('→')) if t.first() == Some(&b'(') => { step(t); PSetS },
+        Some(b!('─')) if t.first() == Some(&b'(') => { step(t); PSet },
         Some(b!('→')) => VSetS(ident(t)), Some(b!('↔')) => VMutS(ident(t)),
         Some(b!('─')) => VSet (ident(t)), Some(b!('═')) => VMut (ident(t)),
         Some(b!(':')) => if let Some(first @ (b'0'..=b'9' | b'-')) = t.first() {
@@ -181,17 +198,26 @@ fn token(first: Option<u8>, t: &mut &[u8]) -> Option<Tok> {
     })
 }
 
-pub fn tokenize(mut t: &[u8]) -> Vec<Tok> {
-    let mut toks = Vec::new();
-    while let Some(mut tok) = token(step(&mut t), &mut t) {
+pub fn tokenize(code: &[u8]) -> Vec<Spanned> {
+    let mut toks: Vec<Spanned> = Vec::new();
+    let mut t = code;
+    let (mut line, mut col, mut pos) = (1, 1, 0);
+    loop {
+        let start = code.len() - t.len();
+        for &c in &code[pos..start] {
+            if c == b'\n' { line += 1; col = 1; } else { col += 1; }
+        }
+        pos = start;
+        let Some(mut tok) = token(step(&mut t), &mut t) else { break };
+        let mut span = Span { start, end: code.len() - t.len(), line, col };
         if let White(_) | Comment(_) = tok { continue; }
         if let VAv2(v, _) = &mut tok {
-            while let Some(VAv1(_)) = toks.last() {
-                let Some(VAv1(l)) = toks.pop() else { unreachable!() };
-                v.push(l);
+            while let Some((VAv1(_), _)) = toks.last() {
+                let Some((VAv1(l), s)) = toks.pop() else { unreachable!() };
+                v.push(l); span = s.to(span);
             }
         }
-        toks.push(tok);
+        toks.push((tok, span));
     }
     toks
 }
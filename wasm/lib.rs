@@ -1,6 +1,6 @@
 use std::cell::{RefCell, RefMut};
 
-use vemf::{self, Env, codepage, Val, bx};
+use vemf::{self, Env, ErrorKind, codepage, Val, bx};
 use wasm_bindgen::prelude::*;
 
 struct Output<'io> {
@@ -47,17 +47,27 @@ impl EvaluateRes {
     }
 }
 
+/// evaluation steps allowed per run, so infinite loops don't freeze the page
+const FUEL: u64 = 100_000_000;
+
 #[wasm_bindgen]
 pub fn evaluate(s: &str, fmt: &str) -> EvaluateRes {
     let outbuf = RefCell::new(Vec::new());
     let mut env = Env::new(bx(rand::thread_rng()));
     env.interface = bx(Output {bufref: &outbuf});
     env.include_stdlib();
+    env.fuel = Some(FUEL);
     let error = env.run_string(s, &fmtstring(fmt));
     env.interface = bx(vemf::NoIO);
-    let borrow = outbuf.borrow();
+    let mut borrow = outbuf.borrow_mut();
+    if let Err(e) = &error {
+        if e.kind != ErrorKind::Exit { borrow.extend(format!("\n{e}").bytes()); }
+    }
 
-    EvaluateRes{ output: String::from_utf8_lossy(&borrow).into_owned(), error: error.err() }
+    EvaluateRes{
+        output: String::from_utf8_lossy(&borrow).into_owned(),
+        error: error.err().map(|e| e.code),
+    }
 }
 
 #[wasm_bindgen]